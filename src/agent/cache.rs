@@ -0,0 +1,176 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    MLResult,
+    agent::{AgentClient, OnToken, Prompt, Provider, StreamingAgentClient, fnv1a},
+    dirs::cache_dir,
+};
+
+/// A cached response, stored as one JSON file in the cache directory
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    provider: String,
+    model: String,
+    request: String,
+    /// When the response was cached, in seconds since the Unix epoch
+    created: u64,
+    response: String,
+}
+
+/// Answers requests the model has already answered from an on-disk cache,
+/// and passes the rest on to another client
+pub struct CachingClient {
+    inner: Arc<dyn StreamingAgentClient>,
+    provider: Provider,
+    model: String,
+    ttl: Duration,
+    dir: PathBuf,
+}
+
+impl CachingClient {
+    pub fn new(
+        inner: Arc<dyn StreamingAgentClient>,
+        provider: Provider,
+        model: String,
+        ttl: Duration,
+        dir: PathBuf,
+    ) -> Self {
+        Self {
+            inner,
+            provider,
+            model,
+            ttl,
+            dir,
+        }
+    }
+
+    /// The cache directory, unless there is no home directory to put it in
+    pub fn default_dir() -> Option<PathBuf> {
+        cache_dir().map(|dir| dir.join("responses"))
+    }
+
+    /// Removes every cached response, returning how many there were
+    pub fn clear(dir: &Path) -> MLResult<usize> {
+        if !dir.exists() {
+            return Ok(0);
+        }
+        let count = std::fs::read_dir(dir)?.count();
+        std::fs::remove_dir_all(dir)?;
+        Ok(count)
+    }
+
+    /// The path of the entry for the prompt. The whole conversation goes into the key,
+    /// so the same request is answered afresh under another system prompt or chat history.
+    fn path(&self, prompt: &Prompt<'_>) -> PathBuf {
+        let key = serde_json::json!([
+            self.provider.to_string(),
+            self.model,
+            prompt.messages,
+            prompt.schema,
+        ]);
+        self.dir
+            .join(format!("{:016x}.json", fnv1a(&key.to_string())))
+    }
+
+    fn get(&self, prompt: &Prompt<'_>) -> Option<String> {
+        let source = std::fs::read_to_string(self.path(prompt)).ok()?;
+        let entry: Entry = serde_json::from_str(&source).ok()?;
+        let age = now().saturating_sub(entry.created);
+        (age < self.ttl.as_secs()).then_some(entry.response)
+    }
+
+    fn put(&self, prompt: &Prompt<'_>, response: &str) {
+        // a response that can't be cached is still a response
+        if let Err(error) = self.write(prompt, response) {
+            tracing::warn!("could not cache the response: {error}");
+        }
+    }
+
+    fn write(&self, prompt: &Prompt<'_>, response: &str) -> MLResult<()> {
+        let entry = Entry {
+            provider: self.provider.to_string(),
+            model: self.model.clone(),
+            request: prompt
+                .messages
+                .last()
+                .map_or(String::new(), |message| message.content.clone()),
+            created: now(),
+            response: response.to_string(),
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(prompt), serde_json::to_string_pretty(&entry)?)?;
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[async_trait::async_trait]
+impl AgentClient for CachingClient {
+    async fn messages<'a>(&self, prompt: Prompt<'a>) -> MLResult<String> {
+        if let Some(response) = self.get(&prompt) {
+            return Ok(response);
+        }
+        let response = self.inner.messages(prompt).await?;
+        self.put(&prompt, &response);
+        Ok(response)
+    }
+}
+
+#[async_trait::async_trait]
+impl StreamingAgentClient for CachingClient {
+    async fn stream_messages<'a>(
+        &self,
+        prompt: Prompt<'a>,
+        on_token: &mut OnToken<'_>,
+    ) -> MLResult<String> {
+        if let Some(response) = self.get(&prompt) {
+            let _ = on_token(&response);
+            return Ok(response);
+        }
+        let response = self.inner.stream_messages(prompt, on_token).await?;
+        self.put(&prompt, &response);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{Conversation, call_agent, mock::MockAgentClient, template::PromptTemplate};
+
+    #[tokio::test]
+    async fn answers_repeated_requests_from_the_cache() {
+        let dir = std::env::temp_dir().join(format!("mathline-cache-{}", std::process::id()));
+        let cache = |inner: MockAgentClient, ttl| {
+            let model = "gemma3:4b".to_string();
+            CachingClient::new(Arc::new(inner), Provider::Ollama, model, ttl, dir.clone())
+        };
+        let model = MockAgentClient::new([("what is two to the tenth?", "2 ** 10")]);
+        let mut conversation = Conversation::new(&PromptTemplate::default()).unwrap();
+        conversation.ask("what is two to the tenth?");
+
+        let hour = Duration::from_secs(3600);
+        let first = call_agent(&cache(model, hour), &conversation).await;
+        // the model no longer knows the answer, so it has to come from the cache
+        let unanswered = MockAgentClient::new::<&str, &str>([]);
+        let second = call_agent(&cache(unanswered, hour), &conversation).await;
+        let unanswered = MockAgentClient::new::<&str, &str>([]);
+        let expired = call_agent(&cache(unanswered, Duration::ZERO), &conversation).await;
+        CachingClient::clear(&dir).unwrap();
+
+        assert_eq!(first.unwrap().expression, "2 ** 10");
+        assert_eq!(second.unwrap().expression, "2 ** 10");
+        assert!(expired.is_err());
+    }
+}
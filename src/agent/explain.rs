@@ -0,0 +1,118 @@
+use serde_json::json;
+
+use crate::{
+    MLResult,
+    agent::{AgentClient, Message, Prompt, Role},
+    error::MathlineError,
+    render,
+    report::Report,
+};
+
+static SYSTEM_PROMPT: &str = "Explain the answer to a math question in two or three sentences, for the person who asked it, with units where they apply. You are given the question, the expression it was translated to, and each step of evaluating it. Use the values from the steps. Never write the final result yourself: write {{answer}} where it belongs, and it will be filled in.";
+
+/// Where the model puts the final result, so the value comes from the evaluator
+static PLACEHOLDER: &str = "{{answer}}";
+
+/// Asks the model to explain how the report answers the question, filling in
+/// the evaluator's value wherever the explanation gives the result.
+/// A report without a value has nothing to explain, so the model isn't asked.
+pub async fn explain<C: AgentClient + ?Sized>(client: &C, report: &Report) -> MLResult<String> {
+    let Some(value) = &report.value else {
+        let error = report
+            .error
+            .as_deref()
+            .unwrap_or("the expression has no value");
+        return Err(MathlineError::NothingToExplain(error.to_string()));
+    };
+    let messages = [
+        Message::new(Role::System, SYSTEM_PROMPT.to_string()),
+        Message::new(Role::User, request(report)),
+    ];
+    let prompt = Prompt {
+        messages: &messages,
+        schema: None,
+    };
+    let explanation = client.messages(prompt).await?;
+    let value = format_value(value);
+    Ok(if explanation.contains(PLACEHOLDER) {
+        explanation.replace(PLACEHOLDER, &value)
+    } else {
+        format!("{} The answer is {value}.", explanation.trim())
+    })
+}
+
+/// The question and its evaluation, as the model is shown them
+fn request(report: &Report) -> String {
+    json!({
+        "question": report.question,
+        "expression": report.expression,
+        "variables": report.variables,
+        "steps": report.steps,
+    })
+    .to_string()
+}
+
+/// Writes a value for prose, without float noise like `0.30000000000000004`,
+/// and with a power of ten when it's too large or small to write out
+fn format_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Number(n) if n.is_f64() => {
+            let (mantissa, exponent) = render::float(n.as_f64().unwrap_or_default(), Some(10));
+            let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+            match exponent {
+                Some(exponent) => format!("{mantissa} × 10^{exponent}"),
+                None => mantissa.to_string(),
+            }
+        }
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{agent::mock::MockAgentClient, parser::ParseOptions};
+
+    #[tokio::test]
+    async fn fills_in_the_evaluated_value() {
+        let mut report =
+            Report::evaluate_isolated("0.1 + 0.2".to_string(), ParseOptions::default()).await;
+        report.question = Some("what is a tenth plus two tenths?".to_string());
+        let client =
+            MockAgentClient::new([(request(&report), "A tenth and two tenths make {{answer}}.")]);
+        let explanation = explain(&client, &report).await.unwrap();
+        assert_eq!(explanation, "A tenth and two tenths make 0.3.");
+
+        let client = MockAgentClient::new([(request(&report), "Add the tenths.")]);
+        let explanation = explain(&client, &report).await.unwrap();
+        assert_eq!(explanation, "Add the tenths. The answer is 0.3.");
+    }
+
+    #[test]
+    fn formats_values_for_prose() {
+        let cases = [
+            (json!(0.30000000000000004), "0.3"),
+            (json!(2.0), "2"),
+            (json!(1e-12), "1 × 10^-12"),
+            (json!(1.5e300), "1.5 × 10^300"),
+            (json!(-2.5e-7), "-2.5 × 10^-7"),
+            (json!(1024), "1024"),
+            (json!("0b101"), "0b101"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(format_value(&value), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_to_explain_a_failed_evaluation() {
+        let report = Report::evaluate_isolated("1 / 0".to_string(), ParseOptions::default()).await;
+        let client = MockAgentClient::new([(request(&report), "It is {{answer}}.")]);
+        let error = explain(&client, &report).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "there is no answer to explain: division by zero"
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{
+    interpreter::{evaluator, solver},
+    parser::{
+        self, Notation, ParseOptions,
+        expression::{Expression, InfixExpression, InfixOp, ValueExpression},
+    },
+};
+
+/// A fenced code block, with an optional language like ```` ```python ````
+static FENCED_BLOCK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)```[ \t]*([\w+-]*)[ \t]*\n(.*?)```").unwrap());
+
+/// Inline code in single backticks, like `` `2 ** 10` ``
+static INLINE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`\n]+)`").unwrap());
+
+/// Display math in `$$ $$` or `\[ \]`
+static DISPLAY_MATH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)\$\$(.+?)\$\$|\\\[(.+?)\\\]").unwrap());
+
+/// Prose that introduces an expression, like "The expression is: 2 ** 10"
+static PROSE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?im)\b(?:expression|formula|equation|calculation|answer)\s+(?:is|would be|becomes)\s*:?[ \t]*(\S.*)$",
+    )
+    .unwrap()
+});
+
+/// A python assignment statement, like `result = 2 ** 10`
+static ASSIGNMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([\p{Alphabetic}_][\p{Alphabetic}\d_]*)\s*=\s*(.+)$").unwrap());
+
+/// How an expression was found in a model's response, from most to least reliable
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// A code block tagged with a language we parse, like ```` ```python ````
+    TaggedBlock,
+    /// A code block with no language
+    BareBlock,
+    /// LaTeX display math like `$$ \frac{1}{2} $$`
+    DisplayMath,
+    /// Inline code like `` `2 ** 10` ``
+    InlineCode,
+    /// A phrase like "The expression is: 2 ** 10"
+    Prose,
+    /// The whole response
+    Response,
+}
+
+impl Strategy {
+    fn base_confidence(&self) -> f64 {
+        match self {
+            Strategy::TaggedBlock => 0.9,
+            Strategy::BareBlock => 0.8,
+            Strategy::DisplayMath => 0.7,
+            Strategy::InlineCode => 0.6,
+            Strategy::Prose => 0.5,
+            Strategy::Response => 0.4,
+        }
+    }
+}
+
+/// An expression found in a model's response
+#[derive(Debug, Clone)]
+pub struct Extraction {
+    pub expression: String,
+    pub strategy: Strategy,
+    /// How likely this is the expression the model meant, from 0 to 1
+    pub confidence: f64,
+}
+
+/// Returns the expression the model most likely meant
+pub fn extract(response: &str) -> Extraction {
+    candidates(response)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| Extraction {
+            expression: response.trim().to_string(),
+            strategy: Strategy::Response,
+            confidence: 0.0,
+        })
+}
+
+/// Returns every expression found in the response, most confident first.
+/// Candidates that parse get a boost, and ones that don't are penalized.
+pub fn candidates(response: &str) -> Vec<Extraction> {
+    let mut candidates = vec![];
+    let mut add = |expression: Option<String>, strategy: Strategy| {
+        let Some(expression) = expression.filter(|expression| !expression.is_empty()) else {
+            return;
+        };
+        let confidence = strategy.base_confidence() * parse_factor(&expression);
+        candidates.push(Extraction {
+            expression,
+            strategy,
+            confidence,
+        });
+    };
+
+    for captures in FENCED_BLOCK.captures_iter(response) {
+        let language = captures[1].to_ascii_lowercase();
+        let code = &captures[2];
+        match language.as_str() {
+            "python" | "py" | "python3" => add(from_python(code), Strategy::TaggedBlock),
+            "latex" | "tex" | "math" => add(Some(code.trim().to_string()), Strategy::TaggedBlock),
+            "" => add(from_python(code), Strategy::BareBlock),
+            // a block in another language, like a shell session, is not an expression
+            _ => {}
+        }
+    }
+    // the rest of the strategies look only outside of code blocks
+    let prose = FENCED_BLOCK.replace_all(response, "\n");
+
+    for captures in DISPLAY_MATH.captures_iter(&prose) {
+        let math = captures.get(1).or_else(|| captures.get(2)).unwrap();
+        add(
+            Some(math.as_str().trim().to_string()),
+            Strategy::DisplayMath,
+        );
+    }
+    for captures in INLINE_CODE.captures_iter(&prose) {
+        add(from_python(&captures[1]), Strategy::InlineCode);
+    }
+    for captures in PROSE.captures_iter(&prose) {
+        add(Some(trim_prose(&captures[1])), Strategy::Prose);
+    }
+    add(from_python(&prose), Strategy::Response);
+
+    // a stable sort keeps earlier candidates first among equals
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    candidates
+}
+
+/// Returns true if the text holds a complete fenced code block
+pub fn has_complete_block(text: &str) -> bool {
+    FENCED_BLOCK.is_match(text)
+}
+
+/// Returns the code in an expression the model fenced or quoted in backticks
+pub fn strip_fences(expression: &str) -> String {
+    match FENCED_BLOCK.captures(expression) {
+        Some(captures) => captures[2].trim().to_string(),
+        None => expression.trim().trim_matches('`').trim().to_string(),
+    }
+}
+
+/// Scales confidence by whether the expression parses as exactly one expression,
+/// and whether it uses only known names, which rules out prose that happens to parse.
+/// Candidates are never evaluated, since that would run whatever the model wrote.
+fn parse_factor(expression: &str) -> f64 {
+    let options = ParseOptions {
+        notation: Notation::Auto,
+        ..Default::default()
+    };
+    match parser::parse(expression, options).as_deref() {
+        Ok([expression])
+            if solver::free_variables(expression).is_empty() && !names_function(expression) =>
+        {
+            1.0
+        }
+        Ok([_]) => 0.8,
+        Ok(_) => 0.6,
+        Err(_) => 0.3,
+    }
+}
+
+/// Returns true for a bare attribute like `math.hypot`, which mentions a function
+/// rather than computing anything
+fn names_function(expression: &Expression) -> bool {
+    matches!(
+        expression,
+        Expression::Infix(InfixExpression { op: InfixOp::Dot, rhs, .. })
+            if matches!(&**rhs, Expression::Value(ValueExpression::Variable(name))
+                if evaluator::constant(name).is_none())
+    )
+}
+
+/// Reduces python code to the expression it computes, inlining assignments so that
+/// `result = 2 ** 10; print(result)` becomes `2 ** 10`
+fn from_python(code: &str) -> Option<String> {
+    let mut assignments: Vec<(String, String)> = vec![];
+    let mut expression = None;
+    for statement in statements(code) {
+        if statement.starts_with("import ") || statement.starts_with("from ") {
+            continue;
+        }
+        let statement = match statement
+            .strip_prefix("print(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            Some(printed) => printed.trim(),
+            None => statement.as_str(),
+        };
+        if let Some(captures) = ASSIGNMENT.captures(statement)
+            && !captures[2].starts_with('=')
+            && !is_equation(&captures[1], &captures[2], &assignments)
+        {
+            let value = inline(&captures[2], &assignments);
+            assignments.push((captures[1].to_string(), value.clone()));
+            expression = Some(value);
+        } else if let Some((_, value)) =
+            assignments.iter().rev().find(|(name, _)| name == statement)
+        {
+            expression = Some(value.clone());
+        } else {
+            expression = Some(inline(statement, &assignments));
+        }
+    }
+    expression
+}
+
+/// Returns true if an assignment is really an equation, like `x = 2*x - 3`, whose name
+/// appears on its own right-hand side without having been assigned before
+fn is_equation(name: &str, value: &str, assignments: &[(String, String)]) -> bool {
+    variable(name).is_match(value) && !assignments.iter().any(|(assigned, _)| assigned == name)
+}
+
+/// Splits code into statements, dropping comments and REPL prompts,
+/// and joining lines inside unclosed brackets
+fn statements(code: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut current = String::new();
+    let mut depth = 0i32;
+    // in a REPL session, lines without a prompt are output
+    let is_session = code.lines().any(|line| line.trim().starts_with(">>>"));
+    for line in code.lines() {
+        let line = line.trim();
+        let line = match line
+            .strip_prefix(">>>")
+            .or_else(|| line.strip_prefix("..."))
+        {
+            Some(line) => line,
+            None if is_session => continue,
+            None => line,
+        };
+        let line = match line.find('#') {
+            Some(i) => &line[..i],
+            None => line,
+        };
+        for part in line.split(';') {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(part.trim());
+            depth += part.matches(['(', '[', '{']).count() as i32;
+            depth -= part.matches([')', ']', '}']).count() as i32;
+            if depth <= 0 {
+                let statement = std::mem::take(&mut current);
+                if !statement.trim().is_empty() {
+                    statements.push(statement.trim().to_string());
+                }
+                depth = 0;
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        statements.push(current.trim().to_string());
+    }
+    statements
+}
+
+/// Replaces each assigned variable in the expression with its value
+fn inline(expression: &str, assignments: &[(String, String)]) -> String {
+    let mut expression = expression.trim().to_string();
+    // later assignments shadow earlier ones
+    for (name, value) in assignments.iter().rev() {
+        let variable = variable(name);
+        let value = if is_atom(value) {
+            value.clone()
+        } else {
+            format!("({value})")
+        };
+        expression = variable
+            .replace_all(&expression, regex::NoExpand(&value))
+            .into_owned();
+    }
+    expression
+}
+
+/// Matches the name as a whole word
+fn variable(name: &str) -> Regex {
+    Regex::new(&format!(r"\b{}\b", regex::escape(name))).unwrap()
+}
+
+/// Returns true if the expression is a single number or name
+fn is_atom(expression: &str) -> bool {
+    expression
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Strips the punctuation and markup around an expression in prose
+fn trim_prose(text: &str) -> String {
+    let text = text.trim().trim_end_matches(['.', ',', ';']).trim();
+    let text = text.trim_matches(['`', '*']);
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Responses from models asked to turn a request into an expression
+    const CORPUS: &[(&str, &str)] = &[
+        ("```python\n2 ** 10\n```", "2 ** 10"),
+        (
+            "```py\n(3 / 4) * math.sqrt(16)\n```",
+            "(3 / 4) * math.sqrt(16)",
+        ),
+        ("```python3\n17 % 5\n```", "17 % 5"),
+        ("```\n365 * 24\n```", "365 * 24"),
+        (
+            "```python\nimport math\n\nexpression = math.sqrt(144) + 3\n```",
+            "math.sqrt(144) + 3",
+        ),
+        (
+            "```python\nresult = (5 + 3) * 2; print(result)\n```",
+            "(5 + 3) * 2",
+        ),
+        (
+            "```python\nprice = 80\ndiscount = 0.25\nfinal = price * (1 - discount)\nprint(final)\n```",
+            "80 * (1 - 0.25)",
+        ),
+        (
+            "```python\nimport math\n\n# area of a circle with radius 3\narea = math.pi * 3 ** 2\nprint(area)\n```",
+            "math.pi * 3 ** 2",
+        ),
+        (
+            "```python\ntotal = (12 +\n         30 +\n         8)\nprint(total)\n```",
+            "(12 + 30 + 8)",
+        ),
+        (">>> 7 * 6\n42", "7 * 6"),
+        ("The expression is: `2 ** 10`", "2 ** 10"),
+        ("You can compute this with `25 * 4`.", "25 * 4"),
+        ("The expression is: 15 / 100 * 80.", "15 / 100 * 80"),
+        (
+            "The mathematical expression would be (100 - 32) * 5 / 9",
+            "(100 - 32) * 5 / 9",
+        ),
+        ("**Expression is:** 12 * 12", "12 * 12"),
+        ("math.factorial(5)", "math.factorial(5)"),
+        ("  (2 + 3) * 4  ", "(2 + 3) * 4"),
+        (
+            "Here is the expression:\n\n```python\n60 * 60 * 24\n```\n\nThis gives the number of seconds in a day.",
+            "60 * 60 * 24",
+        ),
+        (
+            "```python\nmath.sqrt(81)\n```\n\nOr equivalently:\n\n```python\n81 ** 0.5\n```",
+            "math.sqrt(81)",
+        ),
+        (
+            "```bash\npython3 -c 'print(3 + 4)'\n```\n\n```python\n3 + 4\n```",
+            "3 + 4",
+        ),
+        (
+            "Sure! Converting to python:\n```\nimport math\nmath.log(1000, 10)\n```",
+            "math.log(1000, 10)",
+        ),
+        (
+            "```latex\n\\frac{3}{4} \\cdot \\sqrt{16}\n```",
+            "\\frac{3}{4} \\cdot \\sqrt{16}",
+        ),
+        ("$$\\sqrt{2}^{2}$$", "\\sqrt{2}^{2}"),
+        (
+            "The answer is \\[ \\frac{1}{2} + \\frac{1}{3} \\]",
+            "\\frac{1}{2} + \\frac{1}{3}",
+        ),
+        ("```python\nprint(2 ** 0.5)\n```", "2 ** 0.5"),
+        ("```python\nx = 3\ny = x * 2\nx + y\n```", "3 + (3 * 2)"),
+        ("```python\nx = 2*x - 3\n```", "x = 2*x - 3"),
+        ("`a = 2; x = a*x - 3`", "x = 2*x - 3"),
+        ("```python\nn = 3\nn = n + 1\nprint(n)\n```", "3 + 1"),
+        (
+            "To find the hypotenuse we use `math.hypot`... the expression is `math.sqrt(3 ** 2 + 4 ** 2)`",
+            "math.sqrt(3 ** 2 + 4 ** 2)",
+        ),
+    ];
+
+    #[test]
+    fn extracts_corpus() {
+        for (response, expected) in CORPUS {
+            let extraction = extract(response);
+            assert_eq!(
+                extraction.expression, *expected,
+                "extracting from {response:?}, got {extraction:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn confidence_follows_strategy() {
+        let fenced = extract("```python\n2 + 2\n```");
+        let inline = extract("Use `2 + 2`");
+        let prose = extract("The expression is 2 + 2");
+        assert_eq!(fenced.strategy, Strategy::TaggedBlock);
+        assert_eq!(inline.strategy, Strategy::InlineCode);
+        assert_eq!(prose.strategy, Strategy::Prose);
+        assert!(fenced.confidence > inline.confidence);
+        assert!(inline.confidence > prose.confidence);
+    }
+
+    #[test]
+    fn unparsable_candidates_rank_lower() {
+        let extraction = extract("Use `the formula` with `6 * 7`");
+        assert_eq!(extraction.expression, "6 * 7");
+        let candidates = candidates("I cannot turn that into an expression.");
+        assert!(
+            candidates
+                .iter()
+                .all(|candidate| candidate.confidence < 0.5)
+        );
+
+        // ranking doesn't evaluate, so a reply that can't be evaluated is still extracted
+        let extraction = extract(
+            "```python
+1 // 0
+```",
+        );
+        assert_eq!(extraction.expression, "1 // 0");
+        assert_eq!(
+            extraction.confidence,
+            Strategy::TaggedBlock.base_confidence()
+        );
+    }
+}
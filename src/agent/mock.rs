@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    MLResult,
+    agent::{AgentClient, OnToken, Prompt, Role, StreamingAgentClient, fnv1a},
+    error::MathlineError,
+};
+
+/// A recorded response to a request, stored as one JSON file in a fixture directory
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    request: String,
+    response: String,
+}
+
+/// A client that answers each request with a canned response, so the pipeline can run
+/// without a model. Responses are looked up by the last request in the conversation.
+#[derive(Default)]
+pub struct MockAgentClient {
+    responses: HashMap<String, String>,
+}
+
+impl MockAgentClient {
+    #[cfg(test)]
+    pub fn new<R, S>(responses: impl IntoIterator<Item = (R, S)>) -> Self
+    where
+        R: Into<String>,
+        S: Into<String>,
+    {
+        let responses = responses
+            .into_iter()
+            .map(|(request, response)| (request.into(), response.into()))
+            .collect();
+        Self { responses }
+    }
+
+    /// Returns a client answering with the fixtures a [RecordingClient] wrote to `dir`
+    pub fn replay(dir: &Path) -> MLResult<Self> {
+        let mut responses = HashMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let fixture: Fixture = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+                responses.insert(fixture.request, fixture.response);
+            }
+        }
+        Ok(Self { responses })
+    }
+
+    fn response(&self, prompt: &Prompt<'_>) -> MLResult<String> {
+        let request = last_request(prompt);
+        self.responses
+            .get(request)
+            .cloned()
+            .ok_or_else(|| MathlineError::Provider(format!("no mock response for {request:?}")))
+    }
+}
+
+#[async_trait::async_trait]
+impl AgentClient for MockAgentClient {
+    async fn messages<'a>(&self, prompt: Prompt<'a>) -> MLResult<String> {
+        self.response(&prompt)
+    }
+}
+
+#[async_trait::async_trait]
+impl StreamingAgentClient for MockAgentClient {
+    async fn stream_messages<'a>(
+        &self,
+        prompt: Prompt<'a>,
+        on_token: &mut OnToken<'_>,
+    ) -> MLResult<String> {
+        let response = self.response(&prompt)?;
+        // hand the response over a word at a time, like a model would
+        let mut text = String::new();
+        for token in response.split_inclusive(char::is_whitespace) {
+            text.push_str(token);
+            if on_token(token).is_break() {
+                break;
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// Passes requests on to another client, and writes each response to a fixture file
+/// that [MockAgentClient::replay] can answer with later
+pub struct RecordingClient {
+    inner: Arc<dyn StreamingAgentClient>,
+    dir: PathBuf,
+}
+
+impl RecordingClient {
+    pub fn new(inner: Arc<dyn StreamingAgentClient>, dir: PathBuf) -> Self {
+        Self { inner, dir }
+    }
+
+    fn record(&self, request: &str, response: &str) -> MLResult<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let fixture = Fixture {
+            request: request.to_string(),
+            response: response.to_string(),
+        };
+        let path = self.dir.join(format!("{:016x}.json", fnv1a(request)));
+        std::fs::write(path, serde_json::to_string_pretty(&fixture)? + "\n")?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl AgentClient for RecordingClient {
+    async fn messages<'a>(&self, prompt: Prompt<'a>) -> MLResult<String> {
+        let request = last_request(&prompt);
+        let response = self.inner.messages(prompt).await?;
+        self.record(request, &response)?;
+        Ok(response)
+    }
+}
+
+#[async_trait::async_trait]
+impl StreamingAgentClient for RecordingClient {
+    async fn stream_messages<'a>(
+        &self,
+        prompt: Prompt<'a>,
+        on_token: &mut OnToken<'_>,
+    ) -> MLResult<String> {
+        let request = last_request(&prompt);
+        let response = self.inner.stream_messages(prompt, on_token).await?;
+        self.record(request, &response)?;
+        Ok(response)
+    }
+}
+
+fn last_request<'a>(prompt: &Prompt<'a>) -> &'a str {
+    prompt
+        .messages
+        .iter()
+        .rev()
+        .find(|message| message.role == Role::User)
+        .map_or("", |message| message.content.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{Conversation, call_agent, template::PromptTemplate};
+
+    #[tokio::test]
+    async fn replays_recorded_responses() {
+        let dir = std::env::temp_dir().join(format!("mathline-fixtures-{}", std::process::id()));
+        let model = Arc::new(MockAgentClient::new([(
+            "what is two to the tenth?",
+            r#"{"expression": "2 ** 10", "variables": {}}"#,
+        )]));
+        let recorder = RecordingClient::new(model, dir.clone());
+
+        let mut conversation = Conversation::new(&PromptTemplate::default()).unwrap();
+        conversation.ask("what is two to the tenth?");
+        let recorded = call_agent(&recorder, &conversation).await.unwrap();
+        let replayed = call_agent(&MockAgentClient::replay(&dir).unwrap(), &conversation)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(recorded.expression, "2 ** 10");
+        assert_eq!(replayed.expression, "2 ** 10");
+
+        conversation.ask("what is two to the eleventh?");
+        assert!(call_agent(&recorder, &conversation).await.is_err());
+    }
+}
@@ -1,40 +1,412 @@
-use crate::{MLResult, agent::ollama::OllamaClient};
-use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    ops::ControlFlow,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+};
 
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    MLResult,
+    agent::{ollama::OllamaClient, openai::OpenAiClient, template::PromptTemplate},
+    error::MathlineError,
+    interpreter::{evaluator::Evaluator, value::Value},
+    parser::{
+        self, ParseOptions,
+        expression::{Expression, ValueExpression},
+    },
+};
+
+pub mod cache;
+pub mod explain;
+pub mod extract;
+pub mod mock;
 pub mod ollama;
+pub mod openai;
+pub mod stream;
+pub mod template;
+pub mod tools;
+pub mod verify;
+
+/// The JSON schema of [Answer], which the model is asked to follow
+static ANSWER_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    json!({
+        "type": "object",
+        "properties": {
+            "expression": { "type": "string" },
+            "variables": {
+                "type": "object",
+                "additionalProperties": { "type": "number" }
+            },
+            "explanation": { "type": "string" }
+        },
+        "required": ["expression", "variables", "explanation"]
+    })
+});
 
-static SYSTEM_PROMPT: &str = "Transform the following request into a mathematical expression. Do not attempt to solve the expression. Use standard, valid Python syntax.";
+/// One HTTP client for every request, so connections to the provider are reused
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
 
 #[async_trait::async_trait]
-pub trait AgentClient {
+pub trait AgentClient: Send + Sync {
     async fn messages<'a>(&self, prompt: Prompt<'a>) -> MLResult<String>;
 }
 
+/// Called with each piece of a streamed response as it arrives.
+/// Returning [ControlFlow::Break] stops the response early.
+pub type OnToken<'a> = dyn FnMut(&str) -> ControlFlow<()> + Send + 'a;
+
+/// A client that can show its response while the model is still generating it
+#[async_trait::async_trait]
+pub trait StreamingAgentClient: AgentClient {
+    /// Streams the response to `on_token`, and returns all of it
+    async fn stream_messages<'a>(
+        &self,
+        prompt: Prompt<'a>,
+        on_token: &mut OnToken<'_>,
+    ) -> MLResult<String>;
+}
+
+/// A client for a model that can call tools, like the evaluator
+#[async_trait::async_trait]
+pub trait ToolAgentClient: AgentClient {
+    /// Returns the model's next message, which either calls some of the tools
+    /// or answers without them
+    async fn call_tools(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> MLResult<Message>;
+}
+
+#[derive(Clone, Copy)]
 pub struct Prompt<'a> {
-    pub system_prompt: &'a str,
-    pub user_request: &'a str,
+    pub messages: &'a [Message],
+    /// A JSON schema the response must follow, if the provider supports one
+    pub schema: Option<&'a serde_json::Value>,
 }
 
-pub async fn call_agent(user_request: &str, model: &str) -> MLResult<String> {
-    let client = OllamaClient::new(model.to_string());
-    let result = client
-        .messages(Prompt {
-            system_prompt: SYSTEM_PROMPT,
-            user_request,
+/// Who wrote a message in a conversation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    /// The result of a tool the model called
+    Tool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+    /// The tools an assistant message calls
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// The call a tool message is the result of, for providers that give calls ids
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: Role, content: String) -> Self {
+        Self {
+            role,
+            content,
+            tool_calls: vec![],
+            tool_call_id: None,
+        }
+    }
+}
+
+/// A model's request to call a tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// The messages so far in a chat with the model, so that follow-up requests
+/// like "now double that" can refer to earlier answers
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    messages: Vec<Message>,
+}
+
+impl Conversation {
+    /// Returns a conversation holding the template's system prompt,
+    /// and its examples as earlier requests and answers
+    pub fn new(template: &PromptTemplate) -> MLResult<Self> {
+        let mut conversation = Self {
+            messages: vec![Message::new(Role::System, template.system_prompt()?)],
+        };
+        for example in &template.examples {
+            conversation.ask(&example.request);
+            conversation.answered(&example.answer, None);
+        }
+        Ok(conversation)
+    }
+
+    /// Adds the user's next request
+    pub fn ask(&mut self, request: &str) {
+        self.push(Role::User, request.to_string());
+    }
+
+    /// Adds the model's answer to the last request, and the value it evaluated to
+    pub fn answered(&mut self, answer: &Answer, value: Option<&Value>) {
+        let content = serde_json::to_string(answer).unwrap_or_else(|_| answer.expression.clone());
+        self.push(Role::Assistant, content);
+        if let Some(value) = value {
+            let content = format!("The expression {} evaluates to {value}.", answer.expression);
+            self.push(Role::System, content);
+        }
+    }
+
+    fn push(&mut self, role: Role, content: String) {
+        self.messages.push(Message::new(role, content));
+    }
+
+    fn prompt(&self) -> Prompt<'_> {
+        Prompt {
+            messages: &self.messages,
+            schema: Some(&ANSWER_SCHEMA),
+        }
+    }
+}
+
+/// Which LLM API to call
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Provider {
+    /// A local Ollama server
+    #[default]
+    Ollama,
+    /// The OpenAI API, or a compatible server at `OPENAI_BASE_URL`
+    OpenAi,
+}
+
+impl Provider {
+    /// Returns a client for the model from this provider
+    pub fn client(&self, model: &str) -> MLResult<Arc<dyn StreamingAgentClient>> {
+        Ok(match self {
+            Provider::Ollama => Arc::new(OllamaClient::new(model.to_string())),
+            Provider::OpenAi => Arc::new(OpenAiClient::from_env(model.to_string())?),
         })
+    }
+
+    /// Returns a client for the model from this provider that lets the model call tools
+    pub fn tool_client(&self, model: &str) -> MLResult<Arc<dyn ToolAgentClient>> {
+        Ok(match self {
+            Provider::Ollama => Arc::new(OllamaClient::new(model.to_string())),
+            Provider::OpenAi => Arc::new(OpenAiClient::from_env(model.to_string())?),
+        })
+    }
+
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            Provider::Ollama => "gemma3:4b",
+            Provider::OpenAi => "gpt-4o-mini",
+        }
+    }
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provider::Ollama => write!(f, "ollama"),
+            Provider::OpenAi => write!(f, "openai"),
+        }
+    }
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ollama" => Ok(Provider::Ollama),
+            "openai" => Ok(Provider::OpenAi),
+            _ => Err(format!("unknown provider {s}, expected ollama or openai")),
+        }
+    }
+}
+
+/// The model's answer to a request
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Answer {
+    pub expression: String,
+    /// Values for the named quantities in the expression, like `{"price": 80}`
+    #[serde(default)]
+    pub variables: BTreeMap<String, serde_json::Number>,
+    #[serde(default)]
+    pub explanation: Option<String>,
+}
+
+impl Answer {
+    /// Reads the model's JSON answer, or extracts the expression from free text
+    /// when the model didn't follow the schema
+    pub fn from_response(response: &str) -> Self {
+        if let Ok(mut answer) = serde_json::from_str::<Answer>(response)
+            && !answer.expression.trim().is_empty()
+        {
+            // models sometimes fence the expression even inside JSON
+            answer.expression = extract::strip_fences(&answer.expression);
+            return answer;
+        }
+
+        // JSON in some other shape may still hold the expression in one of its strings
+        let extraction = match serde_json::from_str::<serde_json::Value>(response) {
+            Ok(serde_json::Value::Object(object)) => object
+                .values()
+                .filter_map(serde_json::Value::as_str)
+                .map(extract::extract)
+                .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+                .unwrap_or_else(|| extract::extract(response)),
+            _ => extract::extract(response),
+        };
+        tracing::debug!(
+            "extracted {} from {:?} with confidence {:.2}",
+            extraction.expression,
+            extraction.strategy,
+            extraction.confidence
+        );
+        Answer {
+            expression: extraction.expression,
+            variables: BTreeMap::new(),
+            explanation: None,
+        }
+    }
+
+    /// Replaces the answer's variables in the expression with their values
+    pub fn substitute(&self, mut expr: Expression) -> Expression {
+        for (name, value) in &self.variables {
+            let value = match value.as_i64() {
+                Some(n) => ValueExpression::I64(n),
+                None => ValueExpression::F64(value.as_f64().unwrap_or(f64::NAN)),
+            };
+            expr = expr.substitute(name, &Expression::Value(value));
+        }
+        expr
+    }
+
+    /// Evaluates every expression in the answer, returning the value of the last
+    pub fn evaluate(&self, options: ParseOptions) -> MLResult<Value> {
+        let mut value = None;
+        for expr in parser::parse(&self.expression, options)? {
+            value = Some(Evaluator::new(self.substitute(expr)).eval()?);
+        }
+        value.ok_or(MathlineError::InvalidEOF)
+    }
+}
+
+/// Asks the model to answer the last request in the conversation
+pub async fn call_agent<C: AgentClient + ?Sized>(
+    client: &C,
+    conversation: &Conversation,
+) -> MLResult<Answer> {
+    let result = client.messages(conversation.prompt()).await?;
+    Ok(Answer::from_response(&result))
+}
+
+/// Like [call_agent], but passes each piece of the response to `on_token` as it arrives.
+/// Stops the response once it holds a complete code block, since the rest is usually prose.
+pub async fn stream_agent<C: StreamingAgentClient + ?Sized>(
+    client: &C,
+    conversation: &Conversation,
+    on_token: &mut OnToken<'_>,
+) -> MLResult<Answer> {
+    let mut text = String::new();
+    let mut on_token = |token: &str| {
+        text.push_str(token);
+        on_token(token)?;
+        if extract::has_complete_block(&text) {
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    };
+    let result = client
+        .stream_messages(conversation.prompt(), &mut on_token)
         .await?;
+    Ok(Answer::from_response(&result))
+}
+
+/// A hash that stays the same across builds, for naming files after their contents
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
 
-    // If the LLM wraps the code in a code block, use just that code block
-    let re = Regex::new(r"(?s)```python\n(.*?)\n?```").unwrap();
-    if let Some(captures) = re.captures(&result)
-        && let Some(matched) = captures.get(1) {
-            let string = matched
-                .as_str()
-                .to_string()
-                .replace("import math\n\n", "")
-                .replace("expression = ", "");
-            return Ok(string);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_json_answers() {
+        let answer = Answer::from_response(
+            r#"{"expression": "price * (1 - discount)", "variables": {"price": 80, "discount": 0.25}, "explanation": "the discounted price"}"#,
+        );
+        assert_eq!(answer.expression, "price * (1 - discount)");
+        assert_eq!(answer.variables["price"].as_i64(), Some(80));
+        assert_eq!(answer.explanation.as_deref(), Some("the discounted price"));
+    }
+
+    #[test]
+    fn falls_back_to_text() {
+        let cases = [
+            ("```python\n2 ** 10\n```", "2 ** 10"),
+            (r#"{"answer": "2 ** 10"}"#, "2 ** 10"),
+            (r#"{"expression": "```python\n2 ** 10\n```"}"#, "2 ** 10"),
+            (r#"{"expression": "`1 // 0`"}"#, "1 // 0"),
+            (r#"{"expression": "x = 2*x - 3"}"#, "x = 2*x - 3"),
+            (
+                r#"{"expression": "", "explanation": "use `2 ** 10`"}"#,
+                "2 ** 10",
+            ),
+        ];
+        for (response, expected) in cases {
+            let answer = Answer::from_response(response);
+            assert_eq!(answer.expression, expected, "reading {response:?}");
         }
 
-    Ok(result)
+        // an equation written as an assignment is solved rather than inlined
+        let answer = Answer::from_response("```python\nx = 2*x - 3\n```");
+        let value = answer.evaluate(ParseOptions::default()).unwrap();
+        assert_eq!(value.to_string(), "3");
+    }
+
+    #[test]
+    fn conversations_carry_earlier_answers() {
+        let mut conversation = Conversation::new(&PromptTemplate::default()).unwrap();
+        let examples = conversation.messages.len();
+        conversation.ask("what is two to the tenth?");
+        let answer = Answer::from_response(r#"{"expression": "2 ** 10", "variables": {}}"#);
+        conversation.answered(&answer, Some(&Value::I64(1024)));
+        conversation.ask("now double that");
+
+        let roles: Vec<_> = conversation.messages[examples..]
+            .iter()
+            .map(|m| m.role)
+            .collect();
+        assert_eq!(
+            roles,
+            [Role::User, Role::Assistant, Role::System, Role::User]
+        );
+        assert!(
+            conversation.messages[examples + 1]
+                .content
+                .contains("2 ** 10")
+        );
+        assert!(conversation.messages[examples + 2].content.contains("1024"));
+    }
 }
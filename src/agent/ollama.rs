@@ -1,6 +1,10 @@
 use crate::{
     MLResult,
-    agent::{AgentClient, Prompt},
+    agent::{
+        AgentClient, HTTP_CLIENT, Message, OnToken, Prompt, Role, StreamingAgentClient,
+        ToolAgentClient, ToolCall, stream::LineBuffer,
+    },
+    error::MathlineError,
 };
 use serde::{Deserialize, Serialize};
 
@@ -8,47 +12,192 @@ static BASE_URL: &str = "http://localhost:11434";
 
 pub struct OllamaClient {
     model: String,
+    http_client: reqwest::Client,
 }
 
 impl OllamaClient {
     pub fn new(model: String) -> Self {
-        Self { model }
+        Self {
+            model,
+            http_client: HTTP_CLIENT.clone(),
+        }
+    }
+
+    fn request<'a>(&'a self, prompt: &Prompt<'a>, stream: bool) -> OllamaRequest<'a> {
+        OllamaRequest {
+            model: &self.model,
+            messages: prompt.messages,
+            stream,
+            format: prompt.schema,
+            tools: &[],
+        }
+    }
+
+    async fn post(&self, request: &OllamaRequest<'_>) -> MLResult<reqwest::Response> {
+        Ok(self
+            .http_client
+            .post(format!("{BASE_URL}/api/chat"))
+            .json(request)
+            .send()
+            .await?)
+    }
+
+    /// Sends a request that isn't streamed, and returns the model's message
+    async fn chat(&self, request: &OllamaRequest<'_>) -> MLResult<OllamaMessage> {
+        let response = self.post(request).await?;
+        let status = response.status();
+        read_response(status, &response.text().await?)
+    }
+}
+
+/// Reads the body of a response that isn't streamed. Ollama explains a failure,
+/// like a model that isn't pulled, in an `error` field rather than a message.
+fn read_response(status: reqwest::StatusCode, body: &str) -> MLResult<OllamaMessage> {
+    match serde_json::from_str::<OllamaResponse>(body) {
+        Ok(OllamaResponse {
+            error: Some(error), ..
+        }) => Err(MathlineError::Provider(error)),
+        Ok(OllamaResponse {
+            message: Some(message),
+            ..
+        }) => Ok(message),
+        _ if !status.is_success() => Err(MathlineError::Provider(format!("{status}: {body}"))),
+        Ok(_) => Err(MathlineError::EmptyResponse),
+        Err(error) => Err(error.into()),
     }
 }
 
 #[derive(Serialize)]
 struct OllamaRequest<'a> {
     model: &'a str,
-    prompt: &'a str,
-    system: &'a str,
+    messages: &'a [Message],
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tools: &'a [serde_json::Value],
 }
 
 #[derive(Deserialize)]
 struct OllamaResponse {
-    response: String,
+    message: Option<OllamaMessage>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OllamaMessage {
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
+}
+
+/// One line of a streamed response
+#[derive(Deserialize)]
+struct OllamaStreamResponse {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+impl OllamaStreamResponse {
+    fn content(&self) -> &str {
+        self.message
+            .as_ref()
+            .map_or("", |message| message.content.as_str())
+    }
 }
 
 #[async_trait::async_trait]
 impl AgentClient for OllamaClient {
     async fn messages<'a>(&self, prompt: Prompt<'a>) -> MLResult<String> {
-        let http_client = reqwest::Client::new();
+        let request = self.request(&prompt, false);
+        let message = self.chat(&request).await?;
+        Ok(message.content.trim().to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl StreamingAgentClient for OllamaClient {
+    async fn stream_messages<'a>(
+        &self,
+        prompt: Prompt<'a>,
+        on_token: &mut OnToken<'_>,
+    ) -> MLResult<String> {
+        let request = self.request(&prompt, true);
+
+        let mut response = self.post(&request).await?;
+
+        // each line is a JSON object holding the next few tokens
+        let mut lines = LineBuffer::default();
+        let mut text = String::new();
+        while let Some(chunk) = response.chunk().await? {
+            for line in lines.push(&chunk) {
+                let stream_response: OllamaStreamResponse = serde_json::from_str(&line)?;
+                if let Some(error) = stream_response.error {
+                    return Err(MathlineError::Provider(error));
+                }
+                text.push_str(stream_response.content());
+                // dropping the response closes the connection, which stops generation
+                if on_token(stream_response.content()).is_break() || stream_response.done {
+                    return Ok(text.trim().to_string());
+                }
+            }
+        }
+        if let Some(line) = lines.finish() {
+            let stream_response: OllamaStreamResponse = serde_json::from_str(&line)?;
+            text.push_str(stream_response.content());
+            let _ = on_token(stream_response.content());
+        }
 
+        Ok(text.trim().to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl ToolAgentClient for OllamaClient {
+    async fn call_tools(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> MLResult<Message> {
         let request = OllamaRequest {
             model: &self.model,
-            prompt: prompt.user_request,
-            system: prompt.system_prompt,
+            messages,
             stream: false,
+            format: None,
+            tools,
         };
+        let reply = self.chat(&request).await?;
+        let mut message = Message::new(Role::Assistant, reply.content);
+        message.tool_calls = reply.tool_calls;
+        Ok(message)
+    }
+}
 
-        let response = http_client
-            .post(format!("{BASE_URL}/api/generate"))
-            .json(&request)
-            .send()
-            .await?;
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::*;
 
-        let ollama_response: OllamaResponse = response.json().await?;
+    #[test]
+    fn reads_errors_from_responses() {
+        let message = read_response(StatusCode::OK, r#"{"message": {"content": " 2 ** 10 "}}"#);
+        assert_eq!(message.unwrap().content, " 2 ** 10 ");
 
-        Ok(ollama_response.response.trim().to_string())
+        let missing = r#"{"error": "model 'gemma9' not found"}"#;
+        assert!(matches!(
+            read_response(StatusCode::NOT_FOUND, missing),
+            Err(MathlineError::Provider(error)) if error == "model 'gemma9' not found"
+        ));
+        assert!(matches!(
+            read_response(StatusCode::BAD_GATEWAY, "Bad Gateway"),
+            Err(MathlineError::Provider(error)) if error == "502 Bad Gateway: Bad Gateway"
+        ));
+        assert!(matches!(
+            read_response(StatusCode::OK, "{}"),
+            Err(MathlineError::EmptyResponse)
+        ));
     }
 }
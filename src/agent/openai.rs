@@ -0,0 +1,267 @@
+use crate::{
+    MLResult,
+    agent::{
+        AgentClient, FunctionCall, HTTP_CLIENT, Message, OnToken, Prompt, Role,
+        StreamingAgentClient, ToolAgentClient, ToolCall, stream::LineBuffer,
+    },
+    error::MathlineError,
+};
+use serde::{Deserialize, Serialize};
+
+static DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// A client for the OpenAI chat completions API, or any server compatible with it
+pub struct OpenAiClient {
+    model: String,
+    base_url: String,
+    api_key: String,
+    http_client: reqwest::Client,
+}
+
+impl OpenAiClient {
+    /// Returns a client using the `OPENAI_API_KEY` environment variable,
+    /// and `OPENAI_BASE_URL` if it is set
+    pub fn from_env(model: String) -> MLResult<Self> {
+        let api_key = std::env::var("OPENAI_API_KEY")
+            .map_err(|_| MathlineError::MissingEnvVar("OPENAI_API_KEY"))?;
+        let base_url =
+            std::env::var("OPENAI_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Ok(Self {
+            model,
+            base_url,
+            api_key,
+            http_client: HTTP_CLIENT.clone(),
+        })
+    }
+
+    fn request<'a>(&'a self, prompt: &Prompt<'a>, stream: bool) -> ChatRequest<'a> {
+        ChatRequest {
+            model: &self.model,
+            messages: prompt.messages,
+            response_format: prompt.schema.map(|schema| ResponseFormat {
+                r#type: "json_schema",
+                json_schema: JsonSchema {
+                    name: "answer",
+                    schema,
+                },
+            }),
+            stream,
+        }
+    }
+
+    async fn post(&self, request: &impl Serialize) -> MLResult<reqwest::Response> {
+        Ok(self
+            .http_client
+            .post(format!("{}/chat/completions", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(request)
+            .send()
+            .await?
+            .error_for_status()?)
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [Message],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat<'a>>,
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct ResponseFormat<'a> {
+    r#type: &'static str,
+    json_schema: JsonSchema<'a>,
+}
+
+#[derive(Serialize)]
+struct JsonSchema<'a> {
+    name: &'static str,
+    schema: &'a serde_json::Value,
+}
+
+/// A request that lets the model call tools
+#[derive(Serialize)]
+struct ToolChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<WireMessage<'a>>,
+    tools: &'a [serde_json::Value],
+}
+
+/// A message as the API expects it, with tool call arguments as a string of JSON
+#[derive(Serialize)]
+struct WireMessage<'a> {
+    role: Role,
+    content: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<WireToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<&'a str>,
+}
+
+impl<'a> From<&'a Message> for WireMessage<'a> {
+    fn from(message: &'a Message) -> Self {
+        WireMessage {
+            role: message.role,
+            content: &message.content,
+            tool_calls: message.tool_calls.iter().map(WireToolCall::from).collect(),
+            tool_call_id: message.tool_call_id.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireToolCall {
+    id: String,
+    r#type: String,
+    function: WireFunctionCall,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireFunctionCall {
+    name: String,
+    arguments: String,
+}
+
+impl From<&ToolCall> for WireToolCall {
+    fn from(call: &ToolCall) -> Self {
+        WireToolCall {
+            id: call.id.clone().unwrap_or_default(),
+            r#type: "function".to_string(),
+            function: WireFunctionCall {
+                name: call.function.name.clone(),
+                arguments: call.function.arguments.to_string(),
+            },
+        }
+    }
+}
+
+impl From<WireToolCall> for ToolCall {
+    fn from(call: WireToolCall) -> Self {
+        // arguments that aren't valid JSON are passed on as a string for the tool to reject
+        let arguments = serde_json::from_str(&call.function.arguments)
+            .unwrap_or(serde_json::Value::String(call.function.arguments));
+        ToolCall {
+            id: Some(call.id),
+            function: FunctionCall {
+                name: call.function.name,
+                arguments,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatResponseMessage {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<WireToolCall>,
+}
+
+/// One server-sent event of a streamed response
+#[derive(Deserialize)]
+struct ChatStreamChunk {
+    choices: Vec<ChatStreamChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatStreamChoice {
+    delta: ChatResponseMessage,
+}
+
+#[async_trait::async_trait]
+impl AgentClient for OpenAiClient {
+    async fn messages<'a>(&self, prompt: Prompt<'a>) -> MLResult<String> {
+        let request = self.request(&prompt, false);
+        let response = self.post(&request).await?;
+
+        let chat_response: ChatResponse = response.json().await?;
+        let content = chat_response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or(MathlineError::EmptyResponse)?;
+
+        Ok(content.trim().to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl StreamingAgentClient for OpenAiClient {
+    async fn stream_messages<'a>(
+        &self,
+        prompt: Prompt<'a>,
+        on_token: &mut OnToken<'_>,
+    ) -> MLResult<String> {
+        let request = self.request(&prompt, true);
+        let mut response = self.post(&request).await?;
+
+        // each event is a `data: ` line holding the next few tokens, until `data: [DONE]`
+        let mut lines = LineBuffer::default();
+        let mut text = String::new();
+        while let Some(chunk) = response.chunk().await? {
+            for line in lines.push(&chunk) {
+                let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    return Ok(text.trim().to_string());
+                }
+                let stream_chunk: ChatStreamChunk = serde_json::from_str(data)?;
+                let token = stream_chunk
+                    .choices
+                    .into_iter()
+                    .next()
+                    .and_then(|choice| choice.delta.content)
+                    .unwrap_or_default();
+                text.push_str(&token);
+                // dropping the response closes the connection, which stops generation
+                if on_token(&token).is_break() {
+                    return Ok(text.trim().to_string());
+                }
+            }
+        }
+
+        Ok(text.trim().to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl ToolAgentClient for OpenAiClient {
+    async fn call_tools(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> MLResult<Message> {
+        let request = ToolChatRequest {
+            model: &self.model,
+            messages: messages.iter().map(WireMessage::from).collect(),
+            tools,
+        };
+        let response = self.post(&request).await?;
+
+        let chat_response: ChatResponse = response.json().await?;
+        let reply = chat_response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or(MathlineError::EmptyResponse)?
+            .message;
+        let mut message = Message::new(Role::Assistant, reply.content.unwrap_or_default());
+        message.tool_calls = reply.tool_calls.into_iter().map(ToolCall::from).collect();
+        Ok(message)
+    }
+}
@@ -0,0 +1,28 @@
+/// Splits a streamed HTTP body into lines, as used by Ollama's NDJSON and
+/// server-sent events, keeping partial lines until the rest arrives
+#[derive(Default)]
+pub struct LineBuffer {
+    buffer: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Adds a chunk of the body, returning each line it completes
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut lines = vec![];
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Returns the last line, if the body didn't end with a newline
+    pub fn finish(self) -> Option<String> {
+        let line = String::from_utf8_lossy(&self.buffer).trim().to_string();
+        (!line.is_empty()).then_some(line)
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::BTreeMap, path::PathBuf, sync::LazyLock};
+
+use regex::{Captures, Regex};
+use serde::Deserialize;
+
+use crate::{
+    MLResult, agent::Answer, dirs::config_dir, error::MathlineError,
+    interpreter::evaluator::FUNCTIONS,
+};
+
+/// The templates that ship with Mathline, by name
+static BUILTIN: &[(&str, &str)] = &[
+    ("default", include_str!("../../prompts/default.toml")),
+    (
+        "word-problems",
+        include_str!("../../prompts/word-problems.toml"),
+    ),
+    ("latex", include_str!("../../prompts/latex.toml")),
+];
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap());
+
+/// A system prompt with `{{variable}}` placeholders, and example requests
+/// that are shown to the model as earlier turns of the conversation
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptTemplate {
+    pub system: String,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default)]
+    pub examples: Vec<Example>,
+    /// Changes for particular models, by model name or by the name before the `:` tag
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelOverride>,
+}
+
+/// A few-shot example: a request and the answer the model should give
+#[derive(Debug, Clone, Deserialize)]
+pub struct Example {
+    pub request: String,
+    #[serde(flatten)]
+    pub answer: Answer,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ModelOverride {
+    pub system: Option<String>,
+    /// Added to the template's variables, replacing any with the same name
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    /// Replaces the template's examples
+    pub examples: Option<Vec<Example>>,
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self::builtin("default")
+            .and_then(Result::ok)
+            .expect("the default prompt template is valid")
+    }
+}
+
+impl PromptTemplate {
+    /// Loads a template from a TOML file, from `prompts/<name>.toml` in the config directory,
+    /// or from the templates that ship with Mathline, in that order
+    pub fn load(name_or_path: &str) -> MLResult<Self> {
+        let path = PathBuf::from(name_or_path);
+        if path.is_file() {
+            return Self::parse(&std::fs::read_to_string(path)?);
+        }
+        if let Some(path) = config_dir().map(|dir| dir.join(format!("prompts/{name_or_path}.toml")))
+            && path.is_file()
+        {
+            return Self::parse(&std::fs::read_to_string(path)?);
+        }
+        Self::builtin(name_or_path)
+            .ok_or_else(|| MathlineError::UnknownPrompt(name_or_path.to_string()))?
+    }
+
+    fn builtin(name: &str) -> Option<MLResult<Self>> {
+        BUILTIN
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, source)| Self::parse(source))
+    }
+
+    fn parse(source: &str) -> MLResult<Self> {
+        Ok(toml::from_str(source)?)
+    }
+
+    /// Returns the template with the overrides for the model applied
+    pub fn for_model(&self, model: &str) -> Self {
+        let family = model.split_once(':').map_or(model, |(family, _)| family);
+        let Some(changes) = self.models.get(model).or_else(|| self.models.get(family)) else {
+            return self.clone();
+        };
+        let mut template = self.clone();
+        if let Some(system) = &changes.system {
+            template.system = system.clone();
+        }
+        template.variables.extend(changes.variables.clone());
+        if let Some(examples) = &changes.examples {
+            template.examples = examples.clone();
+        }
+        template
+    }
+
+    /// Returns the system prompt with its placeholders filled in
+    pub fn system_prompt(&self) -> MLResult<String> {
+        let functions = function_list();
+        let mut unknown = None;
+        let prompt = PLACEHOLDER.replace_all(&self.system, |captures: &Captures| {
+            let name = &captures[1];
+            match name {
+                "functions" => functions.clone(),
+                _ => self.variables.get(name).cloned().unwrap_or_else(|| {
+                    unknown.get_or_insert_with(|| name.to_string());
+                    String::new()
+                }),
+            }
+        });
+        match unknown {
+            Some(name) => Err(MathlineError::UnknownPromptVariable(name)),
+            None => Ok(prompt.trim().to_string()),
+        }
+    }
+}
+
+/// The signatures of the functions the evaluator supports, like `sqrt(x), cbrt(x)`
+pub fn function_list() -> String {
+    FUNCTIONS
+        .iter()
+        .map(|(signature, _)| *signature)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_templates_render() {
+        for (name, _) in BUILTIN {
+            let template = PromptTemplate::load(name).unwrap();
+            let prompt = template.system_prompt().unwrap();
+            assert!(!prompt.contains("{{"), "{name} left a placeholder");
+            assert!(prompt.contains("sqrt(x)"), "{name} doesn't list functions");
+            assert!(!template.examples.is_empty(), "{name} has no examples");
+        }
+    }
+
+    #[test]
+    fn models_override_the_template() {
+        let template = PromptTemplate::parse(
+            r#"
+            system = "Use {{syntax}}."
+            variables = { syntax = "Python" }
+            examples = [{ request = "two cubed", expression = "2 ** 3" }]
+
+            [models.gemma3]
+            variables = { syntax = "plain Python" }
+            examples = []
+
+            [models."gpt-oss:20b"]
+            system = "Use {{syntax}}, and {{missing}}."
+            "#,
+        )
+        .unwrap();
+
+        let gemma = template.for_model("gemma3:4b");
+        assert_eq!(gemma.system_prompt().unwrap(), "Use plain Python.");
+        assert!(gemma.examples.is_empty());
+
+        let other = template.for_model("llama3");
+        assert_eq!(other.system_prompt().unwrap(), "Use Python.");
+        assert_eq!(other.examples[0].answer.expression, "2 ** 3");
+
+        let gpt = template.for_model("gpt-oss:20b");
+        assert!(matches!(
+            gpt.system_prompt(),
+            Err(MathlineError::UnknownPromptVariable(name)) if name == "missing"
+        ));
+    }
+}
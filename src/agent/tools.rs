@@ -0,0 +1,177 @@
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use crate::{
+    MLResult,
+    agent::{Conversation, Message, Role, ToolAgentClient, ToolCall, template::function_list},
+    error::MathlineError,
+    parser::ParseOptions,
+    report::Report,
+};
+
+/// The most times the model may call tools before it has to answer
+const MAX_ROUNDS: usize = 8;
+
+/// The tool the model calls to evaluate an expression
+static EVALUATE_TOOL: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    json!({
+        "type": "function",
+        "function": {
+            "name": "evaluate",
+            "description": "Evaluates a Python expression exactly, and returns its value \
+                with every intermediate step",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "expression": {
+                        "type": "string",
+                        "description": "The expression, like `(3 + 2 + 11) * 0.25`"
+                    }
+                },
+                "required": ["expression"]
+            }
+        }
+    })
+});
+
+/// Returns a conversation asking the model to work problems out with the evaluate tool
+pub fn conversation() -> Conversation {
+    let system = format!(
+        "Solve the user's problem. Work out every number with the evaluate tool rather than \
+        in your head. Its expressions use Python syntax and may call these functions: {}. \
+        Call it as many times as you need and check the values it returns, then answer \
+        in a sentence or two, with units where they apply.",
+        function_list()
+    );
+    Conversation {
+        messages: vec![Message::new(Role::System, system)],
+    }
+}
+
+/// Lets the model call the evaluator until it answers the last request in the conversation,
+/// passing each evaluation to `on_evaluation`. The calls, their results and the answer
+/// are added to the conversation, and the answer is returned.
+pub async fn solve<C: ToolAgentClient + ?Sized>(
+    client: &C,
+    conversation: &mut Conversation,
+    options: ParseOptions,
+    mut on_evaluation: impl FnMut(&Report),
+) -> MLResult<String> {
+    let tools = [EVALUATE_TOOL.clone()];
+    for _ in 0..MAX_ROUNDS {
+        let reply = client.call_tools(&conversation.messages, &tools).await?;
+        if reply.tool_calls.is_empty() {
+            let answer = reply.content.trim().to_string();
+            conversation.messages.push(reply);
+            return Ok(answer);
+        }
+        let calls = reply.tool_calls.clone();
+        conversation.messages.push(reply);
+        for call in calls {
+            let content = match evaluate(&call, options).await {
+                Ok(report) => {
+                    on_evaluation(&report);
+                    json!(report).to_string()
+                }
+                Err(error) => json!({ "error": error }).to_string(),
+            };
+            let mut result = Message::new(Role::Tool, content);
+            result.tool_call_id = call.id;
+            conversation.messages.push(result);
+        }
+    }
+    Err(MathlineError::TooManyToolCalls(MAX_ROUNDS))
+}
+
+/// Runs a call to the evaluate tool, or explains why it can't be run. The expression
+/// comes from the model, so it's evaluated in isolation like any other untrusted input.
+async fn evaluate(call: &ToolCall, options: ParseOptions) -> Result<Report, String> {
+    if call.function.name != "evaluate" {
+        return Err(format!("there is no tool named {}", call.function.name));
+    }
+    let expression = call.function.arguments["expression"]
+        .as_str()
+        .ok_or("the expression argument must be a string")?;
+    Ok(Report::evaluate_isolated(expression.to_string(), options).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::agent::{AgentClient, FunctionCall, Prompt};
+
+    /// Replies with each of its messages in turn, remembering what it was sent
+    struct ScriptedClient {
+        replies: Mutex<Vec<Message>>,
+        sent: Mutex<Vec<Message>>,
+    }
+
+    #[async_trait::async_trait]
+    impl AgentClient for ScriptedClient {
+        // tool calling never asks for a plain reply
+        async fn messages<'a>(&self, _prompt: Prompt<'a>) -> MLResult<String> {
+            Err(MathlineError::EmptyResponse)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ToolAgentClient for ScriptedClient {
+        async fn call_tools(
+            &self,
+            messages: &[Message],
+            _tools: &[serde_json::Value],
+        ) -> MLResult<Message> {
+            *self.sent.lock().unwrap() = messages.to_vec();
+            Ok(self.replies.lock().unwrap().remove(0))
+        }
+    }
+
+    fn call(expression: &str) -> Message {
+        let mut message = Message::new(Role::Assistant, String::new());
+        message.tool_calls = vec![ToolCall {
+            id: Some(format!("call-{expression}")),
+            function: FunctionCall {
+                name: "evaluate".to_string(),
+                arguments: json!({ "expression": expression }),
+            },
+        }];
+        message
+    }
+
+    #[tokio::test]
+    async fn model_calls_the_evaluator_until_it_answers() {
+        let answer = "Together they have 16 apples, so each gets 4.";
+        let client = ScriptedClient {
+            replies: Mutex::new(vec![
+                call("3 + 2 + 11"),
+                call("16 // 4"),
+                Message::new(Role::Assistant, answer.to_string()),
+            ]),
+            sent: Mutex::default(),
+        };
+        let mut conversation = conversation();
+        conversation.ask("bob has 3 apples, jane 2 and carl 11. how many each if 4 share them?");
+
+        let mut values = vec![];
+        let on_evaluation = |report: &Report| values.push(report.value.clone());
+        let result = solve(
+            &client,
+            &mut conversation,
+            ParseOptions::default(),
+            on_evaluation,
+        );
+        assert_eq!(result.await.unwrap(), answer);
+        assert_eq!(values, [Some(json!(16)), Some(json!(4))]);
+
+        // the model saw each result before its final answer
+        let sent = client.sent.into_inner().unwrap();
+        let results: Vec<_> = sent.iter().filter(|m| m.role == Role::Tool).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].tool_call_id.as_deref(), Some("call-16 // 4"));
+        assert!(results[1].content.contains(r#""value":4"#));
+        assert_eq!(conversation.messages.last().unwrap().content, answer);
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    MLResult,
+    agent::{AgentClient, Answer, Message, Prompt, Role},
+    error::MathlineError,
+    parser::{self, ParseOptions},
+};
+
+static SYSTEM_PROMPT: &str = "You check whether a math expression answers a question. First describe in one plain English sentence what the expression computes, reading only the expression. Then compare that description with the question: does the expression compute what was asked, with the right quantities and operations? If not, say what is wrong in one sentence. Respond with JSON only.";
+
+/// The JSON schema of a [Verdict]
+static VERDICT_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    json!({
+        "type": "object",
+        "properties": {
+            "description": { "type": "string" },
+            "matches": { "type": "boolean" },
+            "critique": { "type": "string" }
+        },
+        "required": ["description", "matches"]
+    })
+});
+
+/// Whether the model judged an expression to answer the question it was translated from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Verdict {
+    /// The expression described back in plain English
+    pub description: String,
+    pub matches: bool,
+    /// What is wrong with the expression, when it doesn't match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critique: Option<String>,
+}
+
+impl Verdict {
+    /// A follow-up request asking the model to translate the question again
+    pub fn retry_request(&self, question: &str) -> String {
+        let critique = self.critique.as_deref().unwrap_or(&self.description);
+        format!("That expression doesn't answer the request: {critique} Try again: {question}")
+    }
+}
+
+/// Asks the model to describe the parsed answer back in words, and judge whether that
+/// matches the question
+pub async fn verify<C: AgentClient + ?Sized>(
+    client: &C,
+    question: &str,
+    answer: &Answer,
+    options: ParseOptions,
+) -> MLResult<Verdict> {
+    let messages = [
+        Message::new(Role::System, SYSTEM_PROMPT.to_string()),
+        Message::new(Role::User, request(question, answer, options)?),
+    ];
+    let prompt = Prompt {
+        messages: &messages,
+        schema: Some(&VERDICT_SCHEMA),
+    };
+    let response = client.messages(prompt).await?;
+    // models without structured output may wrap the JSON in a code block or prose
+    let json = match (response.find('{'), response.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => return Err(MathlineError::UnreadableVerdict(response)),
+    };
+    serde_json::from_str(json).map_err(|_| MathlineError::UnreadableVerdict(response.clone()))
+}
+
+/// The question and the parsed expression, with the answer's variables filled in,
+/// so the judge sees what is evaluated rather than what the model wrote
+fn request(question: &str, answer: &Answer, options: ParseOptions) -> MLResult<String> {
+    let expressions: Vec<_> = parser::parse(&answer.expression, options)?
+        .into_iter()
+        .map(|expr| answer.substitute(expr).to_string())
+        .collect();
+    Ok(json!({
+        "question": question,
+        "expression": expressions.join("; "),
+    })
+    .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::mock::MockAgentClient;
+
+    #[tokio::test]
+    async fn reads_verdicts() {
+        let question = "what is 15% of 80?";
+        let answer = Answer::from_response(r#"{"expression": "80 + 0.15", "variables": {}}"#);
+        let request = request(question, &answer, ParseOptions::default()).unwrap();
+        let client = MockAgentClient::new([(
+            request.clone(),
+            "```json\n{\"description\": \"80 plus 0.15\", \"matches\": false, \"critique\": \"It adds instead of multiplying.\"}\n```",
+        )]);
+        let verdict = verify(&client, question, &answer, ParseOptions::default())
+            .await
+            .unwrap();
+        assert!(!verdict.matches);
+        assert_eq!(
+            verdict.retry_request(question),
+            "That expression doesn't answer the request: It adds instead of multiplying. Try again: what is 15% of 80?"
+        );
+
+        let client = MockAgentClient::new([(request, "Looks right to me.")]);
+        let result = verify(&client, question, &answer, ParseOptions::default()).await;
+        assert!(matches!(result, Err(MathlineError::UnreadableVerdict(_))));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    str::FromStr,
+    sync::Arc,
+};
+
+use serde::Serialize;
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::{
+    MLResult,
+    agent::{
+        AgentClient, Conversation, call_agent,
+        verify::{self, Verdict},
+    },
+    parser::ParseOptions,
+};
+
+/// How batch results are written
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Format {
+    /// `question => value`
+    #[default]
+    Text,
+    /// One JSON object per line
+    Jsonl,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "jsonl" => Ok(Format::Jsonl),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format {s}, expected text, jsonl or csv")),
+        }
+    }
+}
+
+/// The answer to one question of a batch
+#[derive(Serialize)]
+pub struct BatchResult {
+    pub question: String,
+    pub expression: Option<String>,
+    pub value: Option<String>,
+    pub error: Option<String>,
+    /// Whether the expression matched the question, when verifying
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verdict>,
+}
+
+impl BatchResult {
+    /// Writes the result as one line
+    pub fn write(&self, format: Format, out: &mut impl Write) -> MLResult<()> {
+        match format {
+            Format::Text => match (&self.value, &self.error) {
+                (Some(value), _) => match &self.verification {
+                    Some(Verdict {
+                        matches: false,
+                        critique,
+                        ..
+                    }) => writeln!(
+                        out,
+                        "{} => {value} (mismatch: {})",
+                        self.question,
+                        critique.as_deref().unwrap_or_default()
+                    )?,
+                    _ => writeln!(out, "{} => {value}", self.question)?,
+                },
+                (None, error) => writeln!(
+                    out,
+                    "{} => ERROR: {}",
+                    self.question,
+                    error.as_deref().unwrap_or_default()
+                )?,
+            },
+            Format::Jsonl => writeln!(out, "{}", serde_json::to_string(self)?)?,
+            Format::Csv => {
+                let fields = [
+                    Some(self.question.as_str()),
+                    self.expression.as_deref(),
+                    self.value.as_deref(),
+                    self.error.as_deref(),
+                ];
+                let fields: Vec<_> = fields
+                    .into_iter()
+                    .map(|field| csv_field(field.unwrap_or_default()))
+                    .collect();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The header line of a format, if it has one
+pub fn header(format: Format) -> Option<&'static str> {
+    (format == Format::Csv).then_some("question,expression,value,error")
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Reads one question per line from the file, or from stdin if the path is `-`,
+/// skipping blank lines
+pub fn read_questions(path: &str) -> MLResult<Vec<String>> {
+    let source = if path == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    Ok(source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Answers each question on its own, asking the model at most `concurrency` at a time,
+/// and checking each answer that evaluates with [verify::verify] if `verify` is set.
+/// Results are passed to `on_result` in the order of the questions, as soon as all
+/// the earlier ones are done.
+pub async fn run(
+    questions: Vec<String>,
+    conversation: &Conversation,
+    client: Arc<dyn AgentClient>,
+    options: ParseOptions,
+    concurrency: usize,
+    verify: bool,
+    mut on_result: impl FnMut(BatchResult) -> MLResult<()>,
+) -> MLResult<()> {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    // which question each task answers, for reporting a task that panicked
+    let mut spawned = HashMap::new();
+    for (i, question) in questions.into_iter().enumerate() {
+        let mut conversation = conversation.clone();
+        let client = client.clone();
+        let permits = permits.clone();
+        let asked = question.clone();
+        let task = tasks.spawn(async move {
+            let _permit = permits
+                .acquire()
+                .await
+                .expect("the semaphore is never closed");
+            conversation.ask(&question);
+            let answer = call_agent(client.as_ref(), &conversation).await;
+            let mut result = BatchResult {
+                question,
+                expression: None,
+                value: None,
+                error: None,
+                verification: None,
+            };
+            match answer {
+                Ok(answer) => {
+                    match answer.evaluate(options) {
+                        Ok(value) => result.value = Some(value.to_string()),
+                        Err(error) => result.error = Some(error.to_string()),
+                    }
+                    if verify && result.value.is_some() {
+                        let question = &result.question;
+                        match verify::verify(client.as_ref(), question, &answer, options).await {
+                            Ok(verdict) => result.verification = Some(verdict),
+                            Err(error) => result.error = Some(error.to_string()),
+                        }
+                    }
+                    result.expression = Some(answer.expression);
+                }
+                Err(error) => result.error = Some(error.to_string()),
+            }
+            (i, result)
+        });
+        spawned.insert(task.id(), (i, asked));
+    }
+
+    // hold on to results that finish early until the ones before them are done
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let (i, result) = match joined {
+            Ok(finished) => finished,
+            // a panic fails only the question it happened on
+            Err(error) => {
+                let (i, question) = spawned
+                    .remove(&error.id())
+                    .expect("every task is spawned above");
+                let result = BatchResult {
+                    question,
+                    expression: None,
+                    value: None,
+                    error: Some(error.to_string()),
+                    verification: None,
+                };
+                (i, result)
+            }
+        };
+        pending.insert(i, result);
+        while let Some(result) = pending.remove(&next) {
+            on_result(result)?;
+            next += 1;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+/// `$XDG_CONFIG_HOME/mathline`, or `~/.config/mathline`
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_CACHE_HOME/mathline`, or `~/.cache/mathline`
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+fn xdg_dir(var: &str, home_default: &str) -> Option<PathBuf> {
+    let base = match std::env::var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(home_default),
+    };
+    Some(base.join("mathline"))
+}
@@ -6,24 +6,70 @@ pub enum MathlineError {
     Json(#[from] serde_json::Error),
     #[error(transparent)]
     Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    #[error(transparent)]
+    Join(#[from] tokio::task::JoinError),
+    #[error("environment variable {0} is not set")]
+    MissingEnvVar(&'static str),
+    #[error("the model provider returned an error: {0}")]
+    Provider(String),
+    #[error("the model returned an empty response")]
+    EmptyResponse,
+    #[error("the model called tools {0} times without answering")]
+    TooManyToolCalls(usize),
+    #[error("there is no answer to explain: {0}")]
+    NothingToExplain(String),
+    #[error("the model's verdict could not be read: {0}")]
+    UnreadableVerdict(String),
+    #[error("no prompt template named {0}")]
+    UnknownPrompt(String),
+    #[error("the prompt template has no variable {0}")]
+    UnknownPromptVariable(String),
     #[error("invalid char: {0}")]
     InvalidChar(char),
+    #[error("invalid number literal {0}")]
+    InvalidNumber(String),
     #[error("invalid syntax")]
     InvalidSyntax,
     #[error("invalid EOF")]
     InvalidEOF,
     #[error("invalid expression {0}")]
     InvalidExpression(Box<Expression>),
+    #[error("the expression is nested more than {0} levels deep")]
+    NestedTooDeeply(usize),
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("{0} overflows")]
+    Overflow(Box<Expression>),
     #[error("unknown variable {0}")]
     UnknownVariable(String),
     #[error("function {name} cannot have {len} parameters")]
     InvalidFnParameterLength { name: String, len: usize },
     #[error("cannot use a non-numeric value as a number")]
     CannotUseAsNumber,
+    #[error("cannot use a non-integer value as an integer")]
+    CannotUseAsInteger,
+    #[error("cannot use a non-string value as a string")]
+    CannotUseAsString,
     #[error("cannot use a non-boolean value as a bool")]
     CannotUseAsBool,
     #[error("cannot use a non-module value as a module")]
     CannotUseAsModule,
+    #[error("series of {0} terms is too long")]
+    SeriesTooLong(i64),
+    #[error("no value of {0} solves the equation")]
+    NoSolution(String),
+    #[error("every value of {0} solves the equation")]
+    EverySolution(String),
+    #[error("{variable} has {count} solutions, the nearest zero being {nearest}")]
+    TooManySolutions {
+        variable: String,
+        count: usize,
+        nearest: String,
+    },
     #[error("function {0} is not supported")]
     FunctionIsNotSupported(String),
 }
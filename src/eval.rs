@@ -0,0 +1,237 @@
+use std::{
+    fmt::Display,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    MLResult,
+    agent::{AgentClient, Conversation, call_agent, template::PromptTemplate},
+    error::MathlineError,
+    interpreter::{evaluator::Evaluator, value::Value},
+    parser::{self, ParseOptions},
+};
+
+/// One question of an evaluation dataset, and the answer it should get
+#[derive(Debug, Deserialize)]
+pub struct Item {
+    pub question: String,
+    /// A number, bool or string
+    pub expected: serde_json::Value,
+    /// How far a numeric answer may be from the expected one
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f64,
+}
+
+fn default_tolerance() -> f64 {
+    1e-6
+}
+
+impl Item {
+    /// Returns true if the value is the expected answer
+    pub fn accepts(&self, value: &Value) -> bool {
+        match &self.expected {
+            serde_json::Value::Bool(expected) => matches!(value, Value::Bool(b) if b == expected),
+            serde_json::Value::Number(expected) => match (value, expected.as_f64()) {
+                (Value::I64(_) | Value::F64(_), Some(expected)) => value
+                    .as_f64()
+                    .is_ok_and(|v| (v - expected).abs() <= self.tolerance),
+                _ => false,
+            },
+            serde_json::Value::String(expected) => match value {
+                Value::Str(s) => s == expected,
+                _ => value.to_string() == *expected,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Reads a dataset with one JSON [Item] per line, skipping blank lines
+pub fn read_dataset(path: &Path) -> MLResult<Vec<Item>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+/// How far through the pipeline an item got
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Correct,
+    Wrong,
+    /// The model couldn't be reached
+    AgentError,
+    ParseError,
+    EvalError,
+}
+
+/// What happened to one item, as written to the results file
+#[derive(Debug, Serialize)]
+pub struct ItemResult {
+    pub model: String,
+    pub question: String,
+    pub expected: serde_json::Value,
+    pub expression: Option<String>,
+    pub value: Option<String>,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+    /// How long the model took to answer
+    pub latency_ms: u64,
+}
+
+/// The results of one model on a dataset
+pub struct Report {
+    pub model: String,
+    pub results: Vec<ItemResult>,
+}
+
+impl Report {
+    /// The fraction of items with the outcome
+    pub fn rate(&self, outcome: Outcome) -> f64 {
+        self.count(outcome) as f64 / self.results.len().max(1) as f64
+    }
+
+    fn count(&self, outcome: Outcome) -> usize {
+        self.results.iter().filter(|r| r.outcome == outcome).count()
+    }
+
+    /// The latency below which the fraction `p` of the model's answers came back
+    pub fn latency_percentile(&self, p: f64) -> Duration {
+        let mut latencies: Vec<_> = self.results.iter().map(|r| r.latency_ms).collect();
+        latencies.sort_unstable();
+        let index = ((latencies.len() as f64 * p).ceil() as usize).saturating_sub(1);
+        Duration::from_millis(latencies.get(index).copied().unwrap_or_default())
+    }
+
+    pub fn mean_latency(&self) -> Duration {
+        let total: u64 = self.results.iter().map(|r| r.latency_ms).sum();
+        Duration::from_millis(total / self.results.len().max(1) as u64)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.results.len();
+        writeln!(f, "Model: {}", self.model)?;
+        for (label, outcome) in [
+            ("Accuracy", Outcome::Correct),
+            ("Parse failures", Outcome::ParseError),
+            ("Eval failures", Outcome::EvalError),
+            ("Agent failures", Outcome::AgentError),
+        ] {
+            writeln!(
+                f,
+                "{label}: {}/{total} ({:.0}%)",
+                self.count(outcome),
+                self.rate(outcome) * 100.0
+            )?;
+        }
+        write!(
+            f,
+            "Latency: mean {:?}, p50 {:?}, p95 {:?}",
+            self.mean_latency(),
+            self.latency_percentile(0.5),
+            self.latency_percentile(0.95)
+        )
+    }
+}
+
+/// Asks the model each question in turn, and checks its answers
+pub async fn run(
+    items: &[Item],
+    template: &PromptTemplate,
+    client: &dyn AgentClient,
+    model: &str,
+    options: ParseOptions,
+) -> MLResult<Report> {
+    let template = template.for_model(model);
+    let mut results = vec![];
+    for item in items {
+        let mut conversation = Conversation::new(&template)?;
+        conversation.ask(&item.question);
+        let start = Instant::now();
+        let answer = call_agent(client, &conversation).await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        let mut result = ItemResult {
+            model: model.to_string(),
+            question: item.question.clone(),
+            expected: item.expected.clone(),
+            expression: None,
+            value: None,
+            outcome: Outcome::AgentError,
+            error: None,
+            latency_ms,
+        };
+        let answer = match answer {
+            Ok(answer) => answer,
+            Err(error) => {
+                result.error = Some(error.to_string());
+                results.push(result);
+                continue;
+            }
+        };
+        result.expression = Some(answer.expression.clone());
+
+        let evaluated = parser::parse(&answer.expression, options)
+            .map_err(|error| (Outcome::ParseError, error))
+            .and_then(|expressions| {
+                let mut value = None;
+                for expr in expressions {
+                    let mut evaluator = Evaluator::new(answer.substitute(expr));
+                    value = Some(evaluator.eval().map_err(|e| (Outcome::EvalError, e))?);
+                }
+                value.ok_or((Outcome::ParseError, MathlineError::InvalidEOF))
+            });
+        match evaluated {
+            Ok(value) => {
+                result.outcome = if item.accepts(&value) {
+                    Outcome::Correct
+                } else {
+                    Outcome::Wrong
+                };
+                result.value = Some(value.to_string());
+            }
+            Err((outcome, error)) => {
+                result.outcome = outcome;
+                result.error = Some(error.to_string());
+            }
+        }
+        results.push(result);
+    }
+    Ok(Report {
+        model: model.to_string(),
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_accept_answers_within_tolerance() {
+        let item: Item =
+            serde_json::from_str(r#"{"question": "", "expected": 0.333, "tolerance": 0.001}"#)
+                .unwrap();
+        assert!(item.accepts(&Value::F64(1.0 / 3.0)));
+        assert!(!item.accepts(&Value::F64(0.335)));
+        assert!(!item.accepts(&Value::Bool(false)));
+
+        let item: Item = serde_json::from_str(r#"{"question": "", "expected": 1024}"#).unwrap();
+        assert!(item.accepts(&Value::I64(1024)));
+        assert!(item.accepts(&Value::F64(1024.0)));
+
+        let item: Item = serde_json::from_str(r#"{"question": "", "expected": true}"#).unwrap();
+        assert!(item.accepts(&Value::Bool(true)));
+        assert!(!item.accepts(&Value::I64(1)));
+
+        let item: Item = serde_json::from_str(r#"{"question": "", "expected": "0b101"}"#).unwrap();
+        assert!(item.accepts(&Value::Str("0b101".to_string())));
+    }
+}
@@ -1,42 +1,140 @@
+use std::fmt::Display;
+
 use crate::{
     MLResult,
     error::MathlineError,
-    interpreter::value::Value,
+    interpreter::{solver, value::Value},
     parser::expression::{
-        Expression, FnExpression, InfixExpression, InfixOp, PrefixExpression, PrefixOp,
-        ValueExpression,
+        ComparisonExpression, Expression, FnExpression, InfixExpression, InfixOp, PrefixExpression,
+        PrefixOp, SeriesExpression, SeriesOp, ValueExpression,
     },
 };
 
+/// The most terms a sum or product may have
+const MAX_SERIES_LEN: i64 = 100_000;
+
+/// The largest n whose factorial is a finite float
+const MAX_FLOAT_FACTORIAL: i64 = 170;
+
+/// The functions the evaluator supports, as a signature and a description.
+/// Each can also be called on the `math` module, like `math.sqrt(x)`.
+pub const FUNCTIONS: &[(&str, &str)] = &[
+    ("sin(x)", "sine of x radians"),
+    ("cos(x)", "cosine of x radians"),
+    ("tan(x)", "tangent of x radians"),
+    ("asin(x)", "arcsine of x, in radians"),
+    ("acos(x)", "arccosine of x, in radians"),
+    ("atan(x)", "arctangent of x, in radians"),
+    ("log(x[, base])", "logarithm of x, base 10 by default"),
+    ("ln(x)", "natural logarithm of x"),
+    ("exp(x)", "e to the power of x"),
+    ("sqrt(x)", "square root of x"),
+    ("cbrt(x)", "cube root of x"),
+    ("abs(x)", "absolute value of x"),
+    ("factorial(n)", "factorial of the integer n"),
+    ("int(x)", "x truncated to an integer"),
+    ("bin(n)", "the integer n in binary"),
+    ("hex(n)", "the integer n in hexadecimal"),
+    ("oct(n)", "the integer n in octal"),
+    ("bit_count(n)", "number of set bits in the integer n"),
+    ("print(x)", "x itself"),
+    (
+        "solve(equation, x)",
+        "every real x where the equation holds, like solve(3*x + 4 == 19, x)",
+    ),
+];
+
+/// Returns the value of a named constant like `pi`, or the `math` module
+pub fn constant(name: &str) -> Option<Value> {
+    Some(match name {
+        "math" => Value::Module("math"),
+        "pi" | "PI" | "π" => Value::F64(std::f64::consts::PI),
+        "tau" | "TAU" | "τ" => Value::F64(std::f64::consts::TAU),
+        "e" => Value::F64(std::f64::consts::E),
+        "φ" | "ϕ" => Value::F64(1.618033988749895),
+        "inf" | "∞" => Value::F64(f64::INFINITY),
+        "nan" => Value::F64(f64::NAN),
+        _ => return None,
+    })
+}
+
 pub struct Evaluator {
     expression: Expression,
+    steps: Vec<Step>,
+}
+
+/// An intermediate result of an evaluation
+pub struct Step {
+    pub expression: Expression,
+    pub value: Value,
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} => {}", self.expression, self.value)
+    }
 }
 
 impl Evaluator {
     /// Returns a new evaluator for the expression
     pub fn new(expression: Expression) -> Self {
-        Evaluator { expression }
+        Evaluator {
+            expression,
+            steps: vec![],
+        }
+    }
+
+    /// Evaluates the expression. A whole expression that is an equality with one unknown,
+    /// like `3*x + 4 = 19`, is an equation to solve; nested inside anything else,
+    /// `==` is only a comparison.
+    pub fn eval(&mut self) -> MLResult<Value> {
+        self.steps.clear();
+        if let Expression::Infix(InfixExpression {
+            op: InfixOp::Equal, ..
+        }) = &self.expression
+        {
+            let mut variables = solver::free_variables(&self.expression).into_iter();
+            if let (Some(variable), None) = (variables.next(), variables.next()) {
+                let value = solver::solve(&self.expression, &variable)?;
+                self.steps.push(Step {
+                    expression: self.expression.clone(),
+                    value: value.clone(),
+                });
+                return Ok(value);
+            }
+        }
+        self.eval_expr(self.expression.clone())
     }
 
-    /// Evaluates the expression
-    pub fn eval(self) -> MLResult<Value> {
-        Self::eval_expr(self.expression)
+    /// Returns each step of the last evaluation, in the order they were
+    /// evaluated. If the evaluation failed, these are the steps before the error.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
     }
 
-    fn eval_expr(expr: Expression) -> MLResult<Value> {
-        let expr_string = format!("{expr}");
-        let mut print_log = true;
+    fn eval_expr(&mut self, expr: Expression) -> MLResult<Value> {
+        let step_expression = match expr {
+            Expression::Value(_) => None,
+            _ => Some(expr.clone()),
+        };
         let value: Value = match expr {
-            Expression::Value(expr) => {
-                print_log = false;
-                Self::eval_value(expr)
-            }
-            Expression::Fn(expr) => Self::eval_function(expr),
-            Expression::Infix(expr) => Self::eval_infix(expr),
-            Expression::Prefix(expr) => Self::eval_prefix(expr),
+            Expression::Value(expr) => Self::eval_value(expr),
+            Expression::Fn(expr) => self.eval_function(expr),
+            Expression::Infix(expr) => self.eval_infix(expr),
+            Expression::Prefix(expr) => self.eval_prefix(expr),
+            Expression::Comparison(expr) => self.eval_comparison(expr),
+            Expression::Tuple(exprs) => exprs
+                .into_iter()
+                .map(|expr| self.eval_expr(expr))
+                .collect::<MLResult<Vec<_>>>()
+                .map(Value::Tuple),
+            Expression::Series(expr) => self.eval_series(expr),
         }?;
-        if print_log {
-            println!("Step: {expr_string} => {value}");
+        if let Some(expression) = step_expression {
+            self.steps.push(Step {
+                expression,
+                value: value.clone(),
+            });
         }
         Ok(value)
     }
@@ -46,24 +144,44 @@ impl Evaluator {
             ValueExpression::Bool(b) => Value::Bool(b),
             ValueExpression::I64(n) => Value::I64(n),
             ValueExpression::F64(n) => Value::F64(n),
-            ValueExpression::Variable(v) => match v.as_str() {
-                "math" => Value::Module("math"),
-                "pi" | "PI" | "π" => Value::F64(std::f64::consts::PI),
-                "tau" | "TAU" | "τ" => Value::F64(std::f64::consts::TAU),
-                "e" => Value::F64(std::f64::consts::E),
-                _ => return Err(MathlineError::UnknownVariable(v.to_string())),
-            },
+            ValueExpression::Str(s) => Value::Str(s),
+            ValueExpression::Variable(v) => {
+                constant(&v).ok_or_else(|| MathlineError::UnknownVariable(v.to_string()))?
+            }
         })
     }
 
-    fn eval_prefix(expr: PrefixExpression) -> MLResult<Value> {
-        let rhs = Self::eval_expr(*expr.rhs)?;
+    fn eval_prefix(&mut self, expr: PrefixExpression) -> MLResult<Value> {
+        let rhs = self.eval_expr(*expr.rhs)?;
         Ok(match (expr.op, rhs) {
+            (PrefixOp::PlusMinus, rhs) => {
+                let plus = self.eval_prefix(PrefixExpression {
+                    op: PrefixOp::Plus,
+                    rhs: Box::new(Expression::from(rhs.clone())),
+                })?;
+                let minus = self.eval_prefix(PrefixExpression {
+                    op: PrefixOp::Minus,
+                    rhs: Box::new(Expression::from(rhs)),
+                })?;
+                Value::Tuple(vec![plus, minus])
+            }
             (PrefixOp::Not, Value::Bool(bool)) => Value::Bool(!bool),
             (PrefixOp::Plus, Value::I64(n)) => Value::I64(n),
             (PrefixOp::Plus, Value::F64(n)) => Value::F64(n),
-            (PrefixOp::Minus, Value::I64(n)) => Value::I64(-n),
+            (PrefixOp::Minus, Value::I64(n)) => match n.checked_neg() {
+                Some(n) => Value::I64(n),
+                None => {
+                    let rhs = Box::new(Expression::from(Value::I64(n)));
+                    let expr = Expression::Prefix(PrefixExpression {
+                        op: PrefixOp::Minus,
+                        rhs,
+                    });
+                    return Err(MathlineError::Overflow(Box::new(expr)));
+                }
+            },
             (PrefixOp::Minus, Value::F64(n)) => Value::F64(-n),
+            (PrefixOp::BitNot, Value::I64(n)) => Value::I64(!n),
+            (PrefixOp::BitNot, Value::Bool(b)) => Value::I64(!(b as i64)),
             (op, rhs) => {
                 let rhs = Box::new(Expression::from(rhs));
                 let expr = Expression::Prefix(PrefixExpression { op, rhs });
@@ -72,15 +190,52 @@ impl Evaluator {
         })
     }
 
-    fn eval_infix(expr: InfixExpression) -> MLResult<Value> {
-        let lhs = Self::eval_expr(*expr.lhs)?;
-        let rhs = Self::eval_expr(*expr.rhs)?;
-        let value = match expr.op {
+    fn eval_infix(&mut self, expr: InfixExpression) -> MLResult<Value> {
+        if let InfixOp::Dot = expr.op {
+            return self.eval_dot(*expr.lhs, *expr.rhs);
+        }
+        // a chain like `1 + 2 + 3` nests down its left side, which is walked in a loop
+        // rather than recursed into, so a long chain doesn't exhaust the stack.
+        // Each operator's rhs, and the step for each operator but this one, in order.
+        let mut chain = vec![(expr.op, *expr.rhs, None)];
+        let mut lhs = *expr.lhs;
+        let first = loop {
+            match lhs {
+                Expression::Infix(inner) if inner.op != InfixOp::Dot => {
+                    let step = Expression::Infix(inner.clone());
+                    chain.push((inner.op, *inner.rhs, Some(step)));
+                    lhs = *inner.lhs;
+                }
+                first => break first,
+            }
+        };
+        let mut value = self.eval_expr(first)?;
+        for (op, rhs, step) in chain.into_iter().rev() {
+            let rhs = self.eval_expr(rhs)?;
+            value = Self::apply_infix(op, value, rhs)?;
+            if let Some(expression) = step {
+                self.steps.push(Step {
+                    expression,
+                    value: value.clone(),
+                });
+            }
+        }
+        Ok(value)
+    }
+
+    fn apply_infix(op: InfixOp, lhs: Value, rhs: Value) -> MLResult<Value> {
+        let value = match op {
+            InfixOp::PlusMinus => {
+                let plus = Self::apply_infix(InfixOp::Add, lhs.clone(), rhs.clone())?;
+                let minus = Self::apply_infix(InfixOp::Subtract, lhs, rhs)?;
+                Value::Tuple(vec![plus, minus])
+            }
             InfixOp::Add => {
                 if let Value::I64(l) = lhs
                     && let Value::I64(r) = rhs
                 {
-                    Value::I64(l + r)
+                    let result = l.checked_add(r);
+                    Value::I64(result.ok_or_else(|| Self::overflow(op, lhs, rhs))?)
                 } else {
                     let l = lhs.as_f64()?;
                     let r = rhs.as_f64()?;
@@ -91,7 +246,8 @@ impl Evaluator {
                 if let Value::I64(l) = lhs
                     && let Value::I64(r) = rhs
                 {
-                    Value::I64(l - r)
+                    let result = l.checked_sub(r);
+                    Value::I64(result.ok_or_else(|| Self::overflow(op, lhs, rhs))?)
                 } else {
                     let l = lhs.as_f64()?;
                     let r = rhs.as_f64()?;
@@ -102,17 +258,23 @@ impl Evaluator {
                 if let Value::I64(l) = lhs
                     && let Value::I64(r) = rhs
                 {
-                    Value::I64(l * r)
+                    let result = l.checked_mul(r);
+                    Value::I64(result.ok_or_else(|| Self::overflow(op, lhs, rhs))?)
                 } else {
                     let l = lhs.as_f64()?;
                     let r = rhs.as_f64()?;
                     Value::F64(l * r)
                 }
             }
+            // like python, dividing by zero is an error rather than infinity
+            InfixOp::Divide | InfixOp::Modulo if rhs == Value::I64(0) => {
+                return Err(MathlineError::DivisionByZero);
+            }
             InfixOp::Divide => {
+                // i64::MIN / -1 overflows, so it is divided as floats
                 if let Value::I64(l) = lhs
                     && let Value::I64(r) = rhs
-                    && l % r == 0
+                    && l.checked_rem(r) == Some(0)
                 {
                     Value::I64(l / r)
                 } else {
@@ -125,7 +287,8 @@ impl Evaluator {
                 if let Value::I64(l) = lhs
                     && let Value::I64(r) = rhs
                 {
-                    Value::I64(l % r)
+                    // the remainder of i64::MIN / -1 is 0, though the division overflows
+                    Value::I64(l.wrapping_rem(r))
                 } else {
                     let l = lhs.as_f64()?;
                     let r = rhs.as_f64()?;
@@ -138,22 +301,50 @@ impl Evaluator {
                     && r >= 0
                     && r < u32::MAX as i64
                 {
-                    if let Some(result) = l.checked_pow(r as u32) {
-                        Value::I64(result)
-                    } else {
-                        let expr = Expression::Infix(InfixExpression {
-                            op: expr.op,
-                            lhs: Box::new(Expression::from(lhs)),
-                            rhs: Box::new(Expression::from(rhs)),
-                        });
-                        return Err(MathlineError::InvalidExpression(Box::new(expr)));
-                    }
+                    let result = l.checked_pow(r as u32);
+                    Value::I64(result.ok_or_else(|| Self::overflow(op, lhs, rhs))?)
                 } else {
                     let l = lhs.as_f64()?;
                     let r = rhs.as_f64()?;
                     Value::F64(l.powf(r))
                 }
             }
+            InfixOp::BitAnd | InfixOp::BitOr | InfixOp::BitXor => {
+                // like python, bitwise ops on two bools return a bool
+                if let Value::Bool(l) = lhs
+                    && let Value::Bool(r) = rhs
+                {
+                    Value::Bool(match op {
+                        InfixOp::BitAnd => l & r,
+                        InfixOp::BitOr => l | r,
+                        _ => l ^ r,
+                    })
+                } else {
+                    let l = lhs.as_i64()?;
+                    let r = rhs.as_i64()?;
+                    Value::I64(match op {
+                        InfixOp::BitAnd => l & r,
+                        InfixOp::BitOr => l | r,
+                        _ => l ^ r,
+                    })
+                }
+            }
+            InfixOp::ShiftLeft | InfixOp::ShiftRight => {
+                let l = lhs.as_i64()?;
+                let r = rhs.as_i64()?;
+                let result = match op {
+                    InfixOp::ShiftLeft => Self::shift_left(l, r),
+                    _ => Self::shift_right(l, r),
+                };
+                let Some(result) = result else {
+                    // only a negative shift count is invalid, as in python
+                    return Err(match r {
+                        0.. => Self::overflow(op, lhs, rhs),
+                        _ => Self::invalid_infix(op, lhs, rhs),
+                    });
+                };
+                Value::I64(result)
+            }
             InfixOp::And => {
                 let l = lhs.as_bool()?;
                 let r = rhs.as_bool()?;
@@ -164,58 +355,179 @@ impl Evaluator {
                 let r = rhs.as_bool()?;
                 Value::Bool(l || r)
             }
-            InfixOp::Equal => {
-                let l = lhs.as_f64()?;
-                let r = rhs.as_f64()?;
-                Value::Bool(l == r)
-            }
-            InfixOp::NotEqual => {
-                let l = lhs.as_f64()?;
-                let r = rhs.as_f64()?;
-                Value::Bool(l != r)
-            }
-            InfixOp::LessThan => {
-                let l = lhs.as_f64()?;
-                let r = rhs.as_f64()?;
-                Value::Bool(l < r)
-            }
-            InfixOp::LessThanOrEqual => {
-                let l = lhs.as_f64()?;
-                let r = rhs.as_f64()?;
-                Value::Bool(l <= r)
+            InfixOp::Equal
+            | InfixOp::NotEqual
+            | InfixOp::LessThan
+            | InfixOp::LessThanOrEqual
+            | InfixOp::GreaterThan
+            | InfixOp::GreaterThanOrEqual
+            | InfixOp::Is
+            | InfixOp::IsNot
+            | InfixOp::In
+            | InfixOp::NotIn => Value::Bool(Self::compare(&op, &lhs, &rhs)?),
+            InfixOp::Dot => unreachable!("handled by eval_dot"),
+        };
+        Ok(value)
+    }
+
+    /// The error for an integer operation whose result doesn't fit in an i64
+    fn overflow(op: InfixOp, lhs: Value, rhs: Value) -> MathlineError {
+        MathlineError::Overflow(Self::infix(op, lhs, rhs))
+    }
+
+    fn invalid_infix(op: InfixOp, lhs: Value, rhs: Value) -> MathlineError {
+        MathlineError::InvalidExpression(Self::infix(op, lhs, rhs))
+    }
+
+    fn infix(op: InfixOp, lhs: Value, rhs: Value) -> Box<Expression> {
+        Box::new(Expression::Infix(InfixExpression {
+            op,
+            lhs: Box::new(Expression::from(lhs)),
+            rhs: Box::new(Expression::from(rhs)),
+        }))
+    }
+
+    /// Evaluates attribute access. `math.sin(x)` calls `sin(x)`,
+    /// and a method call like `n.bit_count()` calls `bit_count(n)`
+    fn eval_dot(&mut self, lhs: Expression, rhs: Expression) -> MLResult<Value> {
+        let lhs = self.eval_expr(lhs)?;
+        if let Value::Module(_) = lhs {
+            let steps = self.steps.len();
+            let value = self.eval_expr(rhs);
+            // the whole `math.f(x)` step replaces the step for `f(x)`,
+            // while a constant like `math.pi` has no step of its own
+            if value.is_ok() && self.steps.len() > steps {
+                self.steps.pop();
             }
-            InfixOp::GreaterThan => {
-                let l = lhs.as_f64()?;
-                let r = rhs.as_f64()?;
-                Value::Bool(l > r)
+            return value;
+        }
+        let Expression::Fn(mut method) = rhs else {
+            return Err(MathlineError::CannotUseAsModule);
+        };
+        method.parameters.insert(0, Expression::from(lhs));
+        self.eval_function(method)
+    }
+
+    /// Python's `<<`, which errors instead of overflowing
+    fn shift_left(lhs: i64, rhs: i64) -> Option<i64> {
+        if rhs < 0 {
+            return None;
+        }
+        if rhs >= 64 {
+            return (lhs == 0).then_some(0);
+        }
+        let result = lhs << rhs;
+        (result >> rhs == lhs).then_some(result)
+    }
+
+    /// Python's `>>`, an arithmetic shift that never overflows
+    fn shift_right(lhs: i64, rhs: i64) -> Option<i64> {
+        if rhs < 0 {
+            return None;
+        }
+        Some(lhs >> rhs.min(63))
+    }
+
+    fn eval_series(&mut self, expr: SeriesExpression) -> MLResult<Value> {
+        let lower = self.eval_expr(*expr.lower)?.as_i64()?;
+        let upper = self.eval_expr(*expr.upper)?.as_i64()?;
+        let len = upper.saturating_sub(lower).saturating_add(1);
+        if len > MAX_SERIES_LEN {
+            return Err(MathlineError::SeriesTooLong(len));
+        }
+        let (op, mut result) = match expr.op {
+            SeriesOp::Sum => (InfixOp::Add, Value::I64(0)),
+            SeriesOp::Product => (InfixOp::Multiply, Value::I64(1)),
+        };
+        for i in lower..=upper {
+            let term = expr
+                .body
+                .substitute(&expr.variable, &Expression::Value(ValueExpression::I64(i)));
+            let term = self.eval_expr(term)?;
+            result = self.eval_infix(InfixExpression {
+                op: op.clone(),
+                lhs: Box::new(Expression::from(result)),
+                rhs: Box::new(Expression::from(term)),
+            })?;
+        }
+        Ok(result)
+    }
+
+    fn eval_comparison(&mut self, expr: ComparisonExpression) -> MLResult<Value> {
+        let mut lhs = self.eval_expr(*expr.lhs)?;
+        for (op, rhs) in expr.comparisons {
+            let rhs = self.eval_expr(rhs)?;
+            // short-circuit like python: later operands are not evaluated
+            if !Self::compare(&op, &lhs, &rhs)? {
+                return Ok(Value::Bool(false));
             }
-            InfixOp::GreaterThanOrEqual => {
-                let l = lhs.as_f64()?;
-                let r = rhs.as_f64()?;
-                Value::Bool(l >= r)
+            lhs = rhs;
+        }
+        Ok(Value::Bool(true))
+    }
+
+    fn compare(op: &InfixOp, lhs: &Value, rhs: &Value) -> MLResult<bool> {
+        Ok(match op {
+            InfixOp::Equal => lhs == rhs,
+            InfixOp::NotEqual => lhs != rhs,
+            InfixOp::Is => lhs.is(rhs),
+            InfixOp::IsNot => !lhs.is(rhs),
+            InfixOp::In | InfixOp::NotIn => {
+                let Some(contains) = rhs.contains(lhs) else {
+                    return Err(Self::invalid_infix(op.clone(), lhs.clone(), rhs.clone()));
+                };
+                contains == (*op == InfixOp::In)
             }
-            InfixOp::Dot => {
-                lhs.as_module()?;
-                rhs
+            InfixOp::LessThan
+            | InfixOp::LessThanOrEqual
+            | InfixOp::GreaterThan
+            | InfixOp::GreaterThanOrEqual => {
+                if !lhs.is_numeric() || !rhs.is_numeric() {
+                    return Err(MathlineError::CannotUseAsNumber);
+                }
+                match op {
+                    InfixOp::LessThan => lhs < rhs,
+                    InfixOp::LessThanOrEqual => lhs <= rhs,
+                    InfixOp::GreaterThan => lhs > rhs,
+                    _ => lhs >= rhs,
+                }
             }
-        };
-        Ok(value)
+            _ => return Err(MathlineError::InvalidSyntax),
+        })
     }
 
-    fn eval_function(expr: FnExpression) -> MLResult<Value> {
+    fn eval_function(&mut self, expr: FnExpression) -> MLResult<Value> {
+        // the unknown has no value, so the equation can't be evaluated up front
+        if expr.name == "solve" {
+            let (equation, variable) = solver::unknown(&expr.parameters)?;
+            return solver::solve(equation, &variable);
+        }
         let parameters = expr
             .parameters
             .into_iter()
-            .map(Self::eval_expr)
+            .map(|expr| self.eval_expr(expr))
             .collect::<MLResult<Vec<_>>>()?;
 
         match expr.name.as_str() {
             "sin" => Self::eval_sin(parameters),
             "cos" => Self::eval_cos(parameters),
             "tan" => Self::eval_tan(parameters),
-            "log" => Self::eval_log10(parameters),
+            "log" => Self::eval_log(parameters),
             "ln" => Self::eval_ln(parameters),
+            "exp" => Self::eval_exp(parameters),
+            "asin" => Self::eval_asin(parameters),
+            "acos" => Self::eval_acos(parameters),
+            "atan" => Self::eval_atan(parameters),
+            "sqrt" => Self::eval_sqrt(parameters),
+            "abs" => Self::eval_abs(parameters),
+            "factorial" => Self::eval_factorial(parameters),
+            "cbrt" => Self::eval_cbrt(parameters),
             "print" => Self::eval_print(parameters),
+            "bin" => Self::eval_int_to_string("bin", parameters),
+            "hex" => Self::eval_int_to_string("hex", parameters),
+            "oct" => Self::eval_int_to_string("oct", parameters),
+            "int" => Self::eval_int(parameters),
+            "bit_count" => Self::eval_bit_count(parameters),
             _ => Err(MathlineError::FunctionIsNotSupported(expr.name)),
         }
     }
@@ -253,28 +565,146 @@ impl Evaluator {
         Ok(Value::F64(result))
     }
 
-    fn eval_log10(parameters: Vec<Value>) -> MLResult<Value> {
+    fn eval_log(parameters: Vec<Value>) -> MLResult<Value> {
+        let result = match parameters.as_slice() {
+            [n] => n.as_f64()?.log10(),
+            [n, base] => n.as_f64()?.log(base.as_f64()?),
+            _ => {
+                return Err(MathlineError::InvalidFnParameterLength {
+                    name: "log".to_string(),
+                    len: parameters.len(),
+                });
+            }
+        };
+        Ok(Value::F64(result))
+    }
+
+    fn eval_ln(parameters: Vec<Value>) -> MLResult<Value> {
         if parameters.len() != 1 {
             return Err(MathlineError::InvalidFnParameterLength {
-                name: "log".to_string(),
+                name: "ln".to_string(),
                 len: parameters.len(),
             });
         }
-        let result = parameters[0].as_f64()?.log10();
+        let result = parameters[0].as_f64()?.ln();
         Ok(Value::F64(result))
     }
 
-    fn eval_ln(parameters: Vec<Value>) -> MLResult<Value> {
+    fn eval_exp(parameters: Vec<Value>) -> MLResult<Value> {
         if parameters.len() != 1 {
             return Err(MathlineError::InvalidFnParameterLength {
-                name: "ln".to_string(),
+                name: "exp".to_string(),
                 len: parameters.len(),
             });
         }
-        let result = parameters[0].as_f64()?.ln();
+        let result = parameters[0].as_f64()?.exp();
+        Ok(Value::F64(result))
+    }
+
+    fn eval_asin(parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: "asin".to_string(),
+                len: parameters.len(),
+            });
+        }
+        let result = parameters[0].as_f64()?.asin();
+        Ok(Value::F64(result))
+    }
+
+    fn eval_acos(parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: "acos".to_string(),
+                len: parameters.len(),
+            });
+        }
+        let result = parameters[0].as_f64()?.acos();
+        Ok(Value::F64(result))
+    }
+
+    fn eval_atan(parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: "atan".to_string(),
+                len: parameters.len(),
+            });
+        }
+        let result = parameters[0].as_f64()?.atan();
         Ok(Value::F64(result))
     }
 
+    fn eval_sqrt(parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: "sqrt".to_string(),
+                len: parameters.len(),
+            });
+        }
+        let result = parameters[0].as_f64()?.sqrt();
+        Ok(Value::F64(result))
+    }
+
+    fn eval_cbrt(parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: "cbrt".to_string(),
+                len: parameters.len(),
+            });
+        }
+        let result = parameters[0].as_f64()?.cbrt();
+        Ok(Value::F64(result))
+    }
+
+    fn eval_abs(parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: "abs".to_string(),
+                len: parameters.len(),
+            });
+        }
+        match &parameters[0] {
+            Value::F64(n) => Ok(Value::F64(n.abs())),
+            value => {
+                let n = value
+                    .as_i64()
+                    .map_err(|_| MathlineError::CannotUseAsNumber)?;
+                n.checked_abs()
+                    .map(Value::I64)
+                    .ok_or_else(|| Self::invalid_fn("abs", parameters))
+            }
+        }
+    }
+
+    fn eval_factorial(parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: "factorial".to_string(),
+                len: parameters.len(),
+            });
+        }
+        let n = parameters[0].as_i64()?;
+        if n < 0 {
+            return Err(Self::invalid_fn("factorial", parameters));
+        }
+        // fall back to a float once the result no longer fits in an i64,
+        // and to infinity once it no longer fits in a float
+        let result = (1..=n).try_fold(1i64, |acc, i| acc.checked_mul(i));
+        Ok(match result {
+            Some(result) => Value::I64(result),
+            None if n > MAX_FLOAT_FACTORIAL => Value::F64(f64::INFINITY),
+            None => Value::F64((1..=n).map(|i| i as f64).product()),
+        })
+    }
+
+    fn invalid_fn(name: &str, parameters: Vec<Value>) -> MathlineError {
+        let expr = Expression::Fn(FnExpression {
+            name: name.to_string(),
+            parameters: parameters.into_iter().map(Expression::from).collect(),
+        });
+        MathlineError::InvalidExpression(Box::new(expr))
+    }
+
     fn eval_print(parameters: Vec<Value>) -> MLResult<Value> {
         if parameters.len() != 1 {
             return Err(MathlineError::InvalidFnParameterLength {
@@ -282,7 +712,268 @@ impl Evaluator {
                 len: parameters.len(),
             });
         }
-        let result = parameters[0];
+        let result = parameters.into_iter().next().unwrap();
         Ok(result)
     }
+
+    fn eval_int_to_string(name: &str, parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: name.to_string(),
+                len: parameters.len(),
+            });
+        }
+        let n = parameters[0].as_i64()?;
+        let sign = if n < 0 { "-" } else { "" };
+        let n = n.unsigned_abs();
+        let result = match name {
+            "bin" => format!("{sign}0b{n:b}"),
+            "hex" => format!("{sign}0x{n:x}"),
+            _ => format!("{sign}0o{n:o}"),
+        };
+        Ok(Value::Str(result))
+    }
+
+    fn eval_int(parameters: Vec<Value>) -> MLResult<Value> {
+        let result = match parameters.as_slice() {
+            [Value::Str(s)] => Self::parse_int(s, 10)?,
+            [Value::F64(n)] => {
+                // i64::MAX rounds up to 2^63 as a float
+                if !n.is_finite() || n.abs() >= 9_223_372_036_854_775_808.0 {
+                    return Err(MathlineError::CannotUseAsInteger);
+                }
+                n.trunc() as i64
+            }
+            [value] => value.as_i64()?,
+            [string, base] => Self::parse_int(string.as_str()?, base.as_i64()?)?,
+            _ => {
+                return Err(MathlineError::InvalidFnParameterLength {
+                    name: "int".to_string(),
+                    len: parameters.len(),
+                });
+            }
+        };
+        Ok(Value::I64(result))
+    }
+
+    /// Parses an integer string like python's `int(s, base)`.
+    /// Base 0 infers the base from a `0x`, `0o` or `0b` prefix.
+    fn parse_int(string: &str, base: i64) -> MLResult<i64> {
+        let invalid = || MathlineError::InvalidNumber(string.to_string());
+        let trimmed = string.trim().to_ascii_lowercase();
+        let (sign, unsigned) = match trimmed.strip_prefix('-') {
+            Some(unsigned) => ("-", unsigned),
+            None => ("", trimmed.strip_prefix('+').unwrap_or(&trimmed)),
+        };
+        let prefix = unsigned.get(..2);
+        let (radix, digits) = match (base, prefix) {
+            (0 | 16, Some("0x")) => (16, &unsigned[2..]),
+            (0 | 8, Some("0o")) => (8, &unsigned[2..]),
+            (0 | 2, Some("0b")) => (2, &unsigned[2..]),
+            (0, _) => (10, unsigned),
+            (2..=36, _) => (base as u32, unsigned),
+            _ => return Err(invalid()),
+        };
+        // underscores may separate digits, or directly follow a prefix
+        let digits = if digits.len() < unsigned.len() {
+            digits.strip_prefix('_').unwrap_or(digits)
+        } else {
+            digits
+        };
+        if digits.is_empty()
+            || digits.starts_with('_')
+            || digits.ends_with('_')
+            || digits.contains("__")
+            || !digits.chars().all(|c| c == '_' || c.is_digit(radix))
+        {
+            return Err(invalid());
+        }
+        let digits = digits.replace('_', "");
+        i64::from_str_radix(&format!("{sign}{digits}"), radix).map_err(|_| invalid())
+    }
+
+    fn eval_bit_count(parameters: Vec<Value>) -> MLResult<Value> {
+        if parameters.len() != 1 {
+            return Err(MathlineError::InvalidFnParameterLength {
+                name: "bit_count".to_string(),
+                len: parameters.len(),
+            });
+        }
+        let result = parameters[0].as_i64()?.unsigned_abs().count_ones();
+        Ok(Value::I64(result as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParseOptions};
+
+    fn eval_str(input: &str) -> MLResult<Value> {
+        let expr = parser::parse(input, ParseOptions::default())?.remove(0);
+        Evaluator::new(expr).eval()
+    }
+
+    #[test]
+    fn chains_comparisons() {
+        let expr = parser::parse("1 < 2 + 3 < 10", ParseOptions::default())
+            .unwrap()
+            .remove(0);
+        let mut evaluator = Evaluator::new(expr);
+        assert_eq!(evaluator.eval().unwrap().to_string(), "true");
+        // the shared operand is evaluated once, for both comparisons
+        let steps: Vec<_> = evaluator.steps().iter().map(Step::to_string).collect();
+        assert_eq!(steps, ["2 + 3 => 5", "1 < 2 + 3 < 10 => true"]);
+
+        // a false comparison stops the chain before the division
+        let expr = parser::parse("3 < 2 < 1 // 0", ParseOptions::default())
+            .unwrap()
+            .remove(0);
+        let mut evaluator = Evaluator::new(expr);
+        assert_eq!(evaluator.eval().unwrap().to_string(), "false");
+        assert_eq!(evaluator.steps().len(), 1);
+
+        let cases = [
+            ("1 < 2 == 2", "true"),
+            ("1 < 2 == 3", "false"),
+            ("(1 < 2) == True", "true"),
+            ("1 == 1.0 < 2 != 3", "true"),
+            ("5 > 4 >= 4 > 3", "true"),
+            ("5 > 4 >= 5", "false"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                eval_str(input).unwrap().to_string(),
+                expected,
+                "evaluating {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn steps_through_module_access() {
+        let cases: [(&str, &[&str]); 2] = [
+            (
+                "(1 + 2) * math.pi",
+                &[
+                    "1 + 2 => 3",
+                    "math.pi => 3.14159",
+                    "(1 + 2) * math.pi => 9.42478",
+                ],
+            ),
+            (
+                "(1 + 2) * math.sqrt(4)",
+                &[
+                    "1 + 2 => 3",
+                    "math.sqrt(4) => 2.00000",
+                    "(1 + 2) * math.sqrt(4) => 6.00000",
+                ],
+            ),
+        ];
+        for (input, expected) in cases {
+            let expr = parser::parse(input, ParseOptions::default())
+                .unwrap()
+                .remove(0);
+            let mut evaluator = Evaluator::new(expr);
+            evaluator.eval().unwrap();
+            let steps: Vec<_> = evaluator.steps().iter().map(Step::to_string).collect();
+            assert_eq!(steps, expected, "evaluating {input}");
+        }
+    }
+
+    #[test]
+    fn evaluates_integers_and_strings() {
+        let cases = [
+            ("6 & 3", "2"),
+            ("6 | 3", "7"),
+            ("~5", "-6"),
+            ("True & False", "false"),
+            ("1 << 10", "1024"),
+            ("-16 >> 2", "-4"),
+            ("1 << 64", "1 << 64 overflows"),
+            ("1 << -1", "invalid expression 1 << -1"),
+            ("'abc'", "abc"),
+            ("int('ff', 16)", "255"),
+            ("int('0b101', 0)", "5"),
+            ("int(-3.9)", "-3"),
+            ("bin(-5)", "-0b101"),
+            ("hex(255)", "0xff"),
+            ("oct(8)", "0o10"),
+            ("bit_count(-7)", "3"),
+            ("(255).bit_count()", "8"),
+        ];
+        for (input, expected) in cases {
+            let value = eval_str(input).map_or_else(|e| e.to_string(), |v| v.to_string());
+            assert_eq!(value, expected, "evaluating {input}");
+        }
+        let options = ParseOptions {
+            caret_is_xor: true,
+            ..ParseOptions::default()
+        };
+        let expr = parser::parse("5 ^ 3", options).unwrap().remove(0);
+        assert_eq!(Evaluator::new(expr).eval().unwrap().to_string(), "6");
+    }
+
+    #[test]
+    fn tests_membership() {
+        let cases = [
+            ("3 in (1, 2, 3)", "true"),
+            ("3.0 in (1, 2, 3)", "true"),
+            ("4 not in (1, 2, 3)", "true"),
+            ("'ell' in 'hello'", "true"),
+            ("'x' not in 'hello'", "true"),
+            ("not 1 in (1, 2)", "false"),
+            ("1 in (1, 2) in ((1, 2), 3)", "true"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                eval_str(input).unwrap().to_string(),
+                expected,
+                "evaluating {input}"
+            );
+        }
+        for input in ["1 in 2", "1 in 'one'"] {
+            assert!(
+                matches!(eval_str(input), Err(MathlineError::InvalidExpression(_))),
+                "evaluating {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn integer_arithmetic_errors_instead_of_panicking() {
+        for input in ["1 / 0", "1 // 0", "1 % 0", "1.5 / 0", "2 % False"] {
+            assert!(
+                matches!(eval_str(input), Err(MathlineError::DivisionByZero)),
+                "evaluating {input}"
+            );
+        }
+        for input in [
+            "9223372036854775807 + 1",
+            "-9223372036854775807 - 2",
+            "4611686018427387904 * 2",
+            "-(-9223372036854775807 - 1)",
+            "2 ** 63",
+        ] {
+            assert!(
+                matches!(eval_str(input), Err(MathlineError::Overflow(_))),
+                "evaluating {input}"
+            );
+        }
+        let cases = [
+            (
+                "(-9223372036854775807 - 1) / -1",
+                "9223372036854775808.00000",
+            ),
+            ("(-9223372036854775807 - 1) % -1", "0"),
+            ("factorial(1000)", "inf"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                eval_str(input).unwrap().to_string(),
+                expected,
+                "evaluating {input}"
+            );
+        }
+    }
 }
@@ -1,2 +1,3 @@
 pub mod evaluator;
+pub mod solver;
 pub mod value;
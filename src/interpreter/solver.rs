@@ -0,0 +1,880 @@
+//! Solves equations in one unknown: polynomials exactly where their roots are rational
+//! or square roots, and everything else numerically
+
+use std::collections::BTreeSet;
+
+use crate::{
+    MLResult,
+    error::MathlineError,
+    interpreter::{
+        evaluator::{self, Evaluator},
+        value::Value,
+    },
+    parser::expression::{
+        Expression, InfixExpression, InfixOp, PrefixExpression, PrefixOp, ValueExpression,
+    },
+};
+
+/// The highest power of the unknown solved as a polynomial
+const MAX_DEGREE: usize = 32;
+
+/// The largest constant and leading coefficients whose divisors are tried as roots
+const MAX_RATIONAL_ROOT_TERM: i128 = 1_000_000_000_000;
+
+/// The most fractions of divisors tried as roots. Past this, only the fractions
+/// nearest the approximate roots are tried.
+const MAX_RATIONAL_ROOT_CANDIDATES: usize = 10_000;
+
+/// Equations that aren't polynomials are solved between -SEARCH_BOUND and SEARCH_BOUND
+const SEARCH_BOUND: f64 = 1000.0;
+const SEARCH_STEPS: usize = 40_000;
+
+/// The most solutions listed in an answer. A periodic equation like `sin(x) = 0.5` has
+/// hundreds within the search range, and is summarised by the few nearest zero instead.
+const MAX_SOLUTIONS: usize = MAX_DEGREE;
+const SUMMARISED_SOLUTIONS: usize = 3;
+
+/// The largest a sample may be and still count as zero, for an expression that is
+/// zero wherever it is defined
+const ZERO_TOLERANCE: f64 = 1e-12;
+
+/// Returns the variables in the expression that aren't constants like `pi`
+pub fn free_variables(expr: &Expression) -> BTreeSet<String> {
+    let mut variables = BTreeSet::new();
+    collect_variables(expr, &mut variables);
+    variables
+}
+
+fn collect_variables(expr: &Expression, variables: &mut BTreeSet<String>) {
+    match expr {
+        Expression::Value(ValueExpression::Variable(v)) => {
+            if evaluator::constant(v).is_none() {
+                variables.insert(v.clone());
+            }
+        }
+        Expression::Value(_) => {}
+        // the unknown of a nested solve has no value outside it
+        Expression::Fn(expr) if expr.name == "solve" => match unknown(&expr.parameters) {
+            Ok((equation, variable)) => {
+                let mut inner = free_variables(equation);
+                inner.remove(&variable);
+                variables.extend(inner);
+            }
+            Err(_) => expr
+                .parameters
+                .iter()
+                .for_each(|p| collect_variables(p, variables)),
+        },
+        Expression::Fn(expr) => expr
+            .parameters
+            .iter()
+            .for_each(|p| collect_variables(p, variables)),
+        // the right of a dot is an attribute or method name, not a variable
+        Expression::Infix(InfixExpression {
+            op: InfixOp::Dot,
+            lhs,
+            ..
+        }) => collect_variables(lhs, variables),
+        Expression::Infix(expr) => {
+            collect_variables(&expr.lhs, variables);
+            collect_variables(&expr.rhs, variables);
+        }
+        Expression::Prefix(expr) => collect_variables(&expr.rhs, variables),
+        Expression::Comparison(expr) => {
+            collect_variables(&expr.lhs, variables);
+            for (_, rhs) in &expr.comparisons {
+                collect_variables(rhs, variables);
+            }
+        }
+        Expression::Tuple(exprs) => exprs.iter().for_each(|e| collect_variables(e, variables)),
+        Expression::Series(expr) => {
+            collect_variables(&expr.lower, variables);
+            collect_variables(&expr.upper, variables);
+            let mut body = free_variables(&expr.body);
+            body.remove(&expr.variable);
+            variables.extend(body);
+        }
+    }
+}
+
+/// Returns the equation and the variable to solve it for from the parameters of
+/// `solve(equation, x)`, or of `solve(equation)` when the equation has one unknown
+pub fn unknown(parameters: &[Expression]) -> MLResult<(&Expression, String)> {
+    match parameters {
+        [
+            equation,
+            Expression::Value(ValueExpression::Variable(variable)),
+        ] => Ok((equation, variable.clone())),
+        [_, variable] => Err(MathlineError::InvalidExpression(Box::new(variable.clone()))),
+        [equation] => {
+            let mut variables = free_variables(equation).into_iter();
+            match (variables.next(), variables.next()) {
+                (Some(variable), None) => Ok((equation, variable)),
+                (Some(_), Some(other)) => Err(MathlineError::UnknownVariable(other)),
+                (None, _) => Err(MathlineError::InvalidExpression(Box::new(equation.clone()))),
+            }
+        }
+        _ => Err(MathlineError::InvalidFnParameterLength {
+            name: "solve".to_string(),
+            len: parameters.len(),
+        }),
+    }
+}
+
+/// Solves the equation for the variable. An expression that isn't an equation is
+/// solved for where it is zero. Returns the solution, or a tuple of every real
+/// solution in ascending order if there are several.
+pub fn solve(equation: &Expression, variable: &str) -> MLResult<Value> {
+    let difference = match equation {
+        Expression::Infix(InfixExpression {
+            op: InfixOp::Equal,
+            lhs,
+            rhs,
+        }) => Expression::Infix(InfixExpression {
+            op: InfixOp::Subtract,
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+        }),
+        Expression::Infix(expr) if expr.op.is_comparison() => {
+            return Err(MathlineError::InvalidExpression(Box::new(equation.clone())));
+        }
+        Expression::Comparison(_) => {
+            return Err(MathlineError::InvalidExpression(Box::new(equation.clone())));
+        }
+        expr => expr.clone(),
+    };
+    if let Some(other) = free_variables(&difference)
+        .into_iter()
+        .find(|v| v != variable)
+    {
+        return Err(MathlineError::UnknownVariable(other));
+    }
+
+    // a polynomial whose fractions overflow, like `2**100 * x = 1`, is still a polynomial
+    let mut roots = if let Some(coefficients) = polynomial(&difference, variable) {
+        solve_polynomial(coefficients, variable)?
+    } else if let Some(coefficients) = polynomial(&difference, variable) {
+        solve_float_polynomial(coefficients, variable)?
+    } else {
+        solve_numerically(&difference, variable)?
+    };
+    roots.sort_by(|a, b| a.to_f64().total_cmp(&b.to_f64()));
+    roots.dedup_by(|a, b| {
+        let (a, b) = (a.to_f64(), b.to_f64());
+        (a - b).abs() <= 1e-9 * a.abs().max(1.0)
+    });
+    if roots.len() > MAX_SOLUTIONS {
+        let count = roots.len();
+        roots.sort_by(|a, b| a.to_f64().abs().total_cmp(&b.to_f64().abs()));
+        roots.truncate(SUMMARISED_SOLUTIONS);
+        roots.sort_by(|a, b| a.to_f64().total_cmp(&b.to_f64()));
+        let nearest: Vec<_> = roots
+            .into_iter()
+            .map(|root| root.into_value().to_string())
+            .collect();
+        return Err(MathlineError::TooManySolutions {
+            variable: variable.to_string(),
+            count,
+            nearest: nearest.join(", "),
+        });
+    }
+    let mut solutions: Vec<_> = roots.into_iter().map(Root::into_value).collect();
+    match solutions.len() {
+        0 => Err(MathlineError::NoSolution(variable.to_string())),
+        1 => Ok(solutions.remove(0)),
+        _ => Ok(Value::Tuple(solutions)),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Root {
+    Exact(Rational),
+    Approximate(f64),
+}
+
+impl Root {
+    fn to_f64(self) -> f64 {
+        match self {
+            Root::Exact(r) => r.to_f64(),
+            Root::Approximate(x) => x,
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Root::Exact(r) => r.into_value(),
+            // numeric roots land a rounding error away from whole numbers,
+            // and adding zero turns -0.0 into 0.0
+            Root::Approximate(x) if (x - x.round()).abs() < 1e-9 => Value::F64(x.round() + 0.0),
+            Root::Approximate(x) => Value::F64(x),
+        }
+    }
+}
+
+/// A fraction, so polynomials with rational roots are solved without rounding
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rational {
+    num: i128,
+    /// Always positive, and in lowest terms with `num`
+    den: i128,
+}
+
+/// A number polynomial coefficients are built from: an exact [Rational], or an `f64`
+/// once the fractions grow too large for one. Each operation returns None if it overflows.
+trait Coefficient: Copy {
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn from_value(value: &Value) -> Option<Self>;
+    fn add(self, other: Self) -> Option<Self>;
+    fn neg(self) -> Option<Self>;
+    fn mul(self, other: Self) -> Option<Self>;
+    fn div(self, other: Self) -> Option<Self>;
+    fn pow(self, power: Self) -> Option<Self>;
+
+    /// Returns the number as a power of the unknown, if it is a whole number
+    /// no larger than [MAX_DEGREE]
+    fn to_degree(self) -> Option<usize>;
+}
+
+impl Rational {
+    /// Returns `num / den` in lowest terms, or None if `den` is zero
+    fn new(num: i128, den: i128) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        let gcd = gcd(num, den);
+        let (num, den) = (num / gcd, den / gcd);
+        if den < 0 {
+            Some(Self {
+                num: num.checked_neg()?,
+                den: den.checked_neg()?,
+            })
+        } else {
+            Some(Self { num, den })
+        }
+    }
+
+    fn int(n: i128) -> Self {
+        Self { num: n, den: 1 }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    /// Returns the square root, if it is rational too
+    fn sqrt(self) -> Option<Self> {
+        if self.num < 0 {
+            return None;
+        }
+        let (num, den) = (self.num.isqrt(), self.den.isqrt());
+        (num * num == self.num && den * den == self.den).then_some(Self { num, den })
+    }
+
+    fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    fn into_value(self) -> Value {
+        match i64::try_from(self.num) {
+            Ok(n) if self.den == 1 => Value::I64(n),
+            _ => Value::F64(self.to_f64()),
+        }
+    }
+}
+
+impl Coefficient for Rational {
+    const ZERO: Self = Self { num: 0, den: 1 };
+    const ONE: Self = Self { num: 1, den: 1 };
+
+    /// Returns the number as a fraction. A float is taken to be the decimal it
+    /// prints as, so `0.1` is exactly a tenth.
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(_) | Value::I64(_) => Some(Self::int(value.as_i64().ok()?.into())),
+            Value::F64(n) if n.is_finite() => {
+                let text = n.to_string();
+                let (int, frac) = text.split_once('.').unwrap_or((&text, ""));
+                let num = format!("{int}{frac}").parse().ok()?;
+                Self::new(num, 10i128.checked_pow(frac.len() as u32)?)
+            }
+            _ => None,
+        }
+    }
+
+    fn add(self, other: Self) -> Option<Self> {
+        let num = self
+            .num
+            .checked_mul(other.den)?
+            .checked_add(other.num.checked_mul(self.den)?)?;
+        Self::new(num, self.den.checked_mul(other.den)?)
+    }
+
+    fn neg(self) -> Option<Self> {
+        Some(Self {
+            num: self.num.checked_neg()?,
+            den: self.den,
+        })
+    }
+
+    fn mul(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.num.checked_mul(other.num)?,
+            self.den.checked_mul(other.den)?,
+        )
+    }
+
+    fn div(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.num.checked_mul(other.den)?,
+            self.den.checked_mul(other.num)?,
+        )
+    }
+
+    /// Raises the fraction to a whole power, so `2 ** 63` needn't fit in an i64
+    fn pow(self, power: Self) -> Option<Self> {
+        if power.den != 1 {
+            return None;
+        }
+        let exponent = u32::try_from(power.num.unsigned_abs()).ok()?;
+        let (num, den) = (
+            self.num.checked_pow(exponent)?,
+            self.den.checked_pow(exponent)?,
+        );
+        if power.num < 0 {
+            Self::new(den, num)
+        } else {
+            Some(Self { num, den })
+        }
+    }
+
+    fn to_degree(self) -> Option<usize> {
+        let degree = usize::try_from(self.num).ok()?;
+        (self.den == 1 && degree <= MAX_DEGREE).then_some(degree)
+    }
+}
+
+impl Coefficient for f64 {
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_f64().ok().filter(|n| n.is_finite())
+    }
+
+    fn add(self, other: Self) -> Option<Self> {
+        Some(self + other).filter(|n| n.is_finite())
+    }
+
+    fn neg(self) -> Option<Self> {
+        Some(-self)
+    }
+
+    fn mul(self, other: Self) -> Option<Self> {
+        Some(self * other).filter(|n| n.is_finite())
+    }
+
+    fn div(self, other: Self) -> Option<Self> {
+        Some(self / other).filter(|n| n.is_finite())
+    }
+
+    fn pow(self, power: Self) -> Option<Self> {
+        Some(self.powf(power)).filter(|n| n.is_finite())
+    }
+
+    fn to_degree(self) -> Option<usize> {
+        (self.fract() == 0.0 && (0.0..=MAX_DEGREE as f64).contains(&self)).then_some(self as usize)
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    i128::try_from(a).unwrap_or(1)
+}
+
+/// Coefficients, lowest power first
+type Polynomial<C = Rational> = Vec<C>;
+
+/// Returns the expression as a polynomial in the variable, or None if it isn't one,
+/// or its coefficients are too large to hold
+fn polynomial<C: Coefficient>(expr: &Expression, variable: &str) -> Option<Polynomial<C>> {
+    let structural = match expr {
+        Expression::Value(ValueExpression::Variable(v)) if v == variable => {
+            Some(vec![C::ZERO, C::ONE])
+        }
+        Expression::Prefix(PrefixExpression {
+            op: PrefixOp::Plus,
+            rhs,
+        }) => polynomial(rhs, variable),
+        Expression::Prefix(PrefixExpression {
+            op: PrefixOp::Minus,
+            rhs,
+        }) => polynomial(rhs, variable).and_then(|p| negate(&p)),
+        Expression::Infix(expr) => infix_polynomial(expr, variable),
+        _ => None,
+    };
+    // anything else without the variable, like `sqrt(2)`, is a constant
+    structural.or_else(|| {
+        if free_variables(expr).contains(variable) {
+            return None;
+        }
+        let value = Evaluator::new(expr.clone()).eval().ok()?;
+        Some(vec![C::from_value(&value)?])
+    })
+}
+
+fn infix_polynomial<C: Coefficient>(
+    expr: &InfixExpression,
+    variable: &str,
+) -> Option<Polynomial<C>> {
+    let lhs: Polynomial<C> = polynomial(&expr.lhs, variable)?;
+    let rhs: Polynomial<C> = polynomial(&expr.rhs, variable)?;
+    match expr.op {
+        InfixOp::Add => add(&lhs, &rhs),
+        InfixOp::Subtract => add(&lhs, &negate(&rhs)?),
+        InfixOp::Multiply => multiply(&lhs, &rhs),
+        InfixOp::Divide => {
+            let [divisor] = rhs[..] else {
+                return None;
+            };
+            lhs.into_iter().map(|c| c.div(divisor)).collect()
+        }
+        InfixOp::Exponent => {
+            let [power] = rhs[..] else {
+                return None;
+            };
+            if let [base] = lhs[..] {
+                return Some(vec![base.pow(power)?]);
+            }
+            (0..power.to_degree()?).try_fold(vec![C::ONE], |product, _| multiply(&product, &lhs))
+        }
+        _ => None,
+    }
+}
+
+fn add<C: Coefficient>(a: &[C], b: &[C]) -> Option<Polynomial<C>> {
+    let coefficient = |p: &[C], i| p.get(i).copied().unwrap_or(C::ZERO);
+    (0..a.len().max(b.len()))
+        .map(|i| coefficient(a, i).add(coefficient(b, i)))
+        .collect()
+}
+
+fn negate<C: Coefficient>(p: &[C]) -> Option<Polynomial<C>> {
+    p.iter().map(|c| c.neg()).collect()
+}
+
+fn multiply<C: Coefficient>(a: &[C], b: &[C]) -> Option<Polynomial<C>> {
+    let len = a.len() + b.len() - 1;
+    if len > MAX_DEGREE + 1 {
+        return None;
+    }
+    let mut product = vec![C::ZERO; len];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] = product[i + j].add(x.mul(*y)?)?;
+        }
+    }
+    Some(product)
+}
+
+/// Evaluates the polynomial at `x`, or None if that overflows
+fn evaluate(p: &[Rational], x: Rational) -> Option<Rational> {
+    p.iter()
+        .rev()
+        .try_fold(Rational::ZERO, |sum, c| sum.mul(x)?.add(*c))
+}
+
+/// Divides the polynomial by `x - root`, for a root of it
+fn deflate(p: &[Rational], root: Rational) -> Option<Polynomial> {
+    let mut quotient = vec![Rational::ZERO; p.len() - 1];
+    let mut carry = Rational::ZERO;
+    for i in (1..p.len()).rev() {
+        carry = p[i].add(carry.mul(root)?)?;
+        quotient[i - 1] = carry;
+    }
+    Some(quotient)
+}
+
+fn solve_polynomial(mut p: Polynomial, variable: &str) -> MLResult<Vec<Root>> {
+    while p.last().is_some_and(Rational::is_zero) {
+        p.pop();
+    }
+    match p.len() {
+        0 => return Err(MathlineError::EverySolution(variable.to_string())),
+        1 => return Err(MathlineError::NoSolution(variable.to_string())),
+        _ => {}
+    }
+
+    let mut roots = vec![];
+    if p[0].is_zero() {
+        roots.push(Root::Exact(Rational::ZERO));
+        while p[0].is_zero() {
+            p.remove(0);
+        }
+    }
+    for root in rational_roots(&mut p) {
+        roots.push(Root::Exact(root));
+    }
+
+    match p.len() - 1 {
+        0 => {}
+        1 => {
+            if let Some(root) = p[0].neg().and_then(|c| c.div(p[1])) {
+                roots.push(Root::Exact(root));
+            }
+        }
+        2 => roots.extend(quadratic_roots(p[2], p[1], p[0])),
+        _ => {
+            let coefficients: Vec<_> = p.iter().map(|c| c.to_f64()).collect();
+            roots.extend(
+                polynomial_roots(&coefficients)
+                    .into_iter()
+                    .map(Root::Approximate),
+            );
+        }
+    }
+    Ok(roots)
+}
+
+/// Solves a polynomial whose exact coefficients overflowed, approximately
+fn solve_float_polynomial(mut p: Polynomial<f64>, variable: &str) -> MLResult<Vec<Root>> {
+    while p.last() == Some(&0.0) {
+        p.pop();
+    }
+    match p.len() {
+        0 => Err(MathlineError::EverySolution(variable.to_string())),
+        1 => Err(MathlineError::NoSolution(variable.to_string())),
+        _ => Ok(polynomial_roots(&p)
+            .into_iter()
+            .map(Root::Approximate)
+            .collect()),
+    }
+}
+
+/// Finds the rational roots of the polynomial by trying each fraction of a divisor of
+/// its constant term over a divisor of its leading term, and divides them out of it.
+/// With too many such fractions, tries only those nearest its approximate roots.
+fn rational_roots(p: &mut Polynomial) -> Vec<Rational> {
+    let mut roots = vec![];
+    // scale the coefficients to integers, whose divisors can be listed
+    let Some(scale) = p
+        .iter()
+        .try_fold(1i128, |lcm, c| lcm.checked_mul(c.den / gcd(lcm, c.den)))
+    else {
+        return roots;
+    };
+    let (Some(constant), Some(leading)) = (
+        p[0].num.checked_mul(scale / p[0].den),
+        p[p.len() - 1].num.checked_mul(scale / p[p.len() - 1].den),
+    ) else {
+        return roots;
+    };
+    if constant.abs() > MAX_RATIONAL_ROOT_TERM || leading.abs() > MAX_RATIONAL_ROOT_TERM {
+        return roots;
+    }
+
+    let (numerators, denominators) = (divisors(constant), divisors(leading));
+    let candidates: Vec<_> =
+        if numerators.len() * denominators.len() <= MAX_RATIONAL_ROOT_CANDIDATES {
+            numerators
+                .iter()
+                .flat_map(|n| denominators.iter().flat_map(move |d| [(*n, *d), (-n, *d)]))
+                .filter_map(|(n, d)| Rational::new(n, d))
+                .collect()
+        } else {
+            let coefficients: Vec<_> = p.iter().map(|c| c.to_f64()).collect();
+            polynomial_roots(&coefficients)
+                .into_iter()
+                .flat_map(|root| denominators.iter().map(move |d| (root * *d as f64, *d)))
+                .filter(|(n, _)| n.abs() < MAX_RATIONAL_ROOT_TERM as f64)
+                .filter_map(|(n, d)| Rational::new(n.round() as i128, d))
+                .collect()
+        };
+
+    for candidate in candidates {
+        if roots.contains(&candidate) {
+            continue;
+        }
+        // divide out the root as often as it repeats
+        let mut found = false;
+        while p.len() > 1 && evaluate(p, candidate).is_some_and(|y| y.is_zero()) {
+            match deflate(p, candidate) {
+                Some(quotient) => *p = quotient,
+                None => return roots,
+            }
+            found = true;
+        }
+        if found {
+            roots.push(candidate);
+        }
+    }
+    roots
+}
+
+/// The positive divisors of a nonzero integer
+fn divisors(n: i128) -> Vec<i128> {
+    let n = n.abs();
+    let mut divisors = vec![];
+    let mut i = 1;
+    while i * i <= n {
+        if n % i == 0 {
+            divisors.push(i);
+            if i * i != n {
+                divisors.push(n / i);
+            }
+        }
+        i += 1;
+    }
+    divisors
+}
+
+/// The real roots of `ax² + bx + c`, exact when the discriminant is a rational square
+fn quadratic_roots(a: Rational, b: Rational, c: Rational) -> Vec<Root> {
+    let exact = || -> Option<Vec<Root>> {
+        let four_ac = Rational::int(4).mul(a)?.mul(c)?;
+        let discriminant = b.mul(b)?.add(four_ac.neg()?)?;
+        if discriminant.num < 0 {
+            return Some(vec![]);
+        }
+        let two_a = Rational::int(2).mul(a)?;
+        let Some(root) = discriminant.sqrt() else {
+            let (b, two_a) = (b.to_f64(), two_a.to_f64());
+            let root = discriminant.to_f64().sqrt();
+            return Some(vec![
+                Root::Approximate((-b - root) / two_a),
+                Root::Approximate((-b + root) / two_a),
+            ]);
+        };
+        Some(vec![
+            Root::Exact(b.neg()?.add(root.neg()?)?.div(two_a)?),
+            Root::Exact(b.neg()?.add(root)?.div(two_a)?),
+        ])
+    };
+    exact().unwrap_or_else(|| {
+        let coefficients = [c.to_f64(), b.to_f64(), a.to_f64()];
+        polynomial_roots(&coefficients)
+            .into_iter()
+            .map(Root::Approximate)
+            .collect()
+    })
+}
+
+/// Finds every real root of a polynomial of degree one or more. Between consecutive
+/// roots of its derivative the polynomial only rises or only falls, so each of those
+/// intervals holds at most one root, which bisection finds.
+fn polynomial_roots(p: &[f64]) -> Vec<f64> {
+    let degree = p.len() - 1;
+    if degree == 1 {
+        return vec![-p[0] / p[1]];
+    }
+    let value = |x: f64| Some(p.iter().rev().fold(0.0, |sum, c| sum * x + c));
+    // every root lies within the Cauchy bound
+    let bound = 1.0
+        + p[..degree]
+            .iter()
+            .map(|c| (c / p[degree]).abs())
+            .fold(0.0, f64::max);
+    let derivative: Vec<_> = p
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| c * i as f64)
+        .collect();
+
+    let mut points = vec![-bound];
+    points.extend(
+        polynomial_roots(&derivative)
+            .into_iter()
+            .filter(|x| x.abs() < bound),
+    );
+    points.push(bound);
+    let mut roots: Vec<_> = points
+        .windows(2)
+        .filter_map(|pair| bisect(value, pair[0], pair[1]))
+        .collect();
+    // a root the polynomial touches without crossing is also a root of the derivative
+    let scale = p.iter().map(|c| c.abs()).fold(0.0, f64::max);
+    for &x in &points[1..points.len() - 1] {
+        let tolerance = 1e-9 * scale * x.abs().max(1.0).powi(degree as i32);
+        if value(x).is_some_and(|y| y.abs() <= tolerance) {
+            roots.push(x);
+        }
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Finds where the expression crosses zero, by sampling it across the search range
+/// and bisecting each sign change
+fn solve_numerically(difference: &Expression, variable: &str) -> MLResult<Vec<Root>> {
+    let value = |x: f64| {
+        let expr = difference.substitute(variable, &Expression::Value(ValueExpression::F64(x)));
+        let value = Evaluator::new(expr).eval().ok()?.as_f64().ok()?;
+        value.is_finite().then_some(value)
+    };
+    let step = 2.0 * SEARCH_BOUND / SEARCH_STEPS as f64;
+    let samples: Vec<_> = (0..=SEARCH_STEPS)
+        .map(|i| {
+            let x = -SEARCH_BOUND + i as f64 * step;
+            (x, value(x))
+        })
+        .collect();
+    // an expression like `x / x - 1` is zero wherever it is defined
+    let mut defined = samples.iter().filter_map(|(_, y)| *y).peekable();
+    if defined.peek().is_some() && defined.all(|y| y.abs() <= ZERO_TOLERANCE) {
+        return Err(MathlineError::EverySolution(variable.to_string()));
+    }
+    let roots = samples
+        .windows(2)
+        .filter(|pair| match (pair[0].1, pair[1].1) {
+            (Some(a), Some(b)) => a.signum() != b.signum() || a == 0.0,
+            _ => false,
+        })
+        .filter_map(|pair| bisect(value, pair[0].0, pair[1].0))
+        // a sign change across a pole, like 1/x has at 0, isn't a root
+        .filter(|&x| value(x).is_some_and(|y| y.abs() < 1e-6))
+        .map(Root::Approximate)
+        .collect();
+    Ok(roots)
+}
+
+/// Narrows down where `f` changes sign between `a` and `b`, if it does
+fn bisect(f: impl Fn(f64) -> Option<f64>, mut a: f64, mut b: f64) -> Option<f64> {
+    let (mut fa, fb) = (f(a)?, f(b)?);
+    if fa == 0.0 {
+        return Some(a);
+    }
+    if fb == 0.0 {
+        return Some(b);
+    }
+    if fa.signum() == fb.signum() {
+        return None;
+    }
+    loop {
+        let mid = (a + b) / 2.0;
+        if mid == a || mid == b {
+            return Some(mid);
+        }
+        let fm = f(mid)?;
+        if fm == 0.0 {
+            return Some(mid);
+        }
+        if fm.signum() == fa.signum() {
+            (a, fa) = (mid, fm);
+        } else {
+            b = mid;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{self, ParseOptions};
+
+    fn solve_str(input: &str) -> MLResult<String> {
+        let expr = parser::parse(input, ParseOptions::default())?.remove(0);
+        Evaluator::new(expr).eval().map(|value| value.to_string())
+    }
+
+    #[test]
+    fn solves_polynomials_with_many_divisors_quickly() {
+        let start = std::time::Instant::now();
+        assert_eq!(
+            solve_str("963761198400*x**3 + x + 963761198400 = 0").unwrap(),
+            "-1.00000"
+        );
+        // the rational roots are still found exactly
+        assert_eq!(
+            solve_str("(963761198400*x - 1) * (x - 963761198400) * (x + 1) = 0").unwrap(),
+            "(-1, 0.00000, 963761198400)"
+        );
+        assert!(start.elapsed().as_secs() < 5, "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn solves_equations() {
+        let cases = [
+            ("3*x + 4 = 19", "5"),
+            ("solve(3*x + 4 == 19, x)", "5"),
+            ("solve(x / 4 == 0.5)", "2"),
+            ("solve(x**2 - 5*x + 6 = 0, x)", "(2, 3)"),
+            ("solve((x - 1) ** 2, x)", "1"),
+            ("x**2 = 2", "(-1.41421, 1.41421)"),
+            (
+                "solve(2*x**3 - 3*x**2 - 3*x + 2 = 0, x)",
+                "(-1, 0.50000, 2)",
+            ),
+            ("x**5 - x - 1 = 0", "1.16730"),
+            ("2 ** x = 1024", "10.00000"),
+            ("solve(1 / x == 4, x) * 8", "2.00000"),
+            ("solve(y**2 == 9, y)", "(-3, 3)"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(solve_str(input).unwrap(), expected, "solving {input}");
+        }
+
+        assert!(matches!(
+            solve_str("x**2 = -1"),
+            Err(MathlineError::NoSolution(_))
+        ));
+        for input in ["2*x = x + x", "x / x == 1", "sin(x)**2 + cos(x)**2 = 1"] {
+            assert!(
+                matches!(solve_str(input), Err(MathlineError::EverySolution(_))),
+                "solving {input}"
+            );
+        }
+        let error = solve_str("sin(x) = 0.5").unwrap_err();
+        assert!(matches!(
+            error,
+            MathlineError::TooManySolutions { count: 637, .. }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "x has 637 solutions, the nearest zero being -3.66519, 0.52360, 2.61799"
+        );
+        assert!(matches!(
+            solve_str("x + y = 3"),
+            Err(MathlineError::UnknownVariable(_))
+        ));
+
+        let expr = parser::parse("3*x + 4 = 19", ParseOptions::default()).unwrap();
+        let mut evaluator = Evaluator::new(expr[0].clone());
+        evaluator.eval().unwrap();
+        let steps: Vec<_> = evaluator.steps().iter().map(ToString::to_string).collect();
+        assert_eq!(steps, ["3 * x + 4 == 19 => 5"]);
+
+        // coefficients too large for an i64, or for exact fractions at all
+        let solve_f64 = |input: &str| {
+            let expr = parser::parse(input, ParseOptions::default()).unwrap();
+            Evaluator::new(expr[0].clone())
+                .eval()
+                .unwrap()
+                .as_f64()
+                .unwrap()
+        };
+        assert_eq!(solve_f64("2**63*x = 1"), 2f64.powi(-63));
+        assert_eq!(solve_f64("x / 2**63 = 2"), 2f64.powi(64));
+        let root = solve_f64("2**100*x = 3");
+        assert!(
+            (root / (3.0 * 2f64.powi(-100)) - 1.0).abs() < 1e-12,
+            "{root}"
+        );
+
+        // only a whole expression is an equation; nested, `==` compares
+        for input in [
+            "x == 1 and True",
+            "(x == 1) + 1",
+            "prcie * 2 == 10 or False",
+        ] {
+            assert!(
+                matches!(solve_str(input), Err(MathlineError::UnknownVariable(_))),
+                "evaluating {input}"
+            );
+        }
+    }
+}
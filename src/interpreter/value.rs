@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{cmp::Ordering, fmt::Display};
 
 use crate::{
     MLResult,
@@ -6,45 +6,161 @@ use crate::{
     parser::expression::{Expression, ValueExpression},
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum Value {
     Bool(bool),
     I64(i64),
     F64(f64),
+    Str(String),
+    Tuple(Vec<Value>),
     Module(&'static str),
 }
 
 impl Value {
-    pub fn as_bool(self) -> MLResult<bool> {
+    pub fn as_bool(&self) -> MLResult<bool> {
         match self {
-            Value::Bool(b) => Ok(b),
-            Value::I64(_) | Value::F64(_) | Value::Module(_) => Err(MathlineError::CannotUseAsBool),
+            Value::Bool(b) => Ok(*b),
+            _ => Err(MathlineError::CannotUseAsBool),
         }
     }
 
-    pub fn as_f64(self) -> MLResult<f64> {
+    pub fn as_f64(&self) -> MLResult<f64> {
         match self {
-            Value::I64(n) => Ok(n as f64),
-            Value::F64(n) => Ok(n),
-            Value::Bool(_) | Value::Module(_) => Err(MathlineError::CannotUseAsNumber),
+            Value::I64(n) => Ok(*n as f64),
+            Value::F64(n) => Ok(*n),
+            _ => Err(MathlineError::CannotUseAsNumber),
         }
     }
 
-    pub fn as_module(self) -> MLResult<&'static str> {
+    /// Returns the value as an integer. Like python, bools are integers
+    pub fn as_i64(&self) -> MLResult<i64> {
+        match self {
+            Value::Bool(b) => Ok(*b as i64),
+            Value::I64(n) => Ok(*n),
+            _ => Err(MathlineError::CannotUseAsInteger),
+        }
+    }
+
+    pub fn as_str(&self) -> MLResult<&str> {
+        if let Value::Str(s) = self {
+            Ok(s)
+        } else {
+            Err(MathlineError::CannotUseAsString)
+        }
+    }
+
+    /// Returns true if the value can be ordered against other numbers.
+    /// Like python, bools are numbers.
+    pub fn is_numeric(&self) -> bool {
+        self.as_numeric().is_some()
+    }
+
+    /// Python's `is` operator. Values are immutable, so identity means
+    /// the same type and the same contents
+    pub fn is(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::I64(l), Value::I64(r)) => l == r,
+            (Value::F64(l), Value::F64(r)) => l.to_bits() == r.to_bits(),
+            (Value::Str(l), Value::Str(r)) => l == r,
+            (Value::Tuple(l), Value::Tuple(r)) => {
+                l.len() == r.len() && l.iter().zip(r).all(|(l, r)| l.is(r))
+            }
+            (Value::Module(l), Value::Module(r)) => l == r,
+            _ => false,
+        }
+    }
+
+    /// Python's `in` operator: whether a tuple holds the item, or a string holds
+    /// the item as a substring. Returns None for values that hold nothing.
+    pub fn contains(&self, item: &Value) -> Option<bool> {
+        match (self, item) {
+            // like python, an item that is the element counts even if it isn't equal to it
+            (Value::Tuple(values), _) => Some(values.iter().any(|v| v.is(item) || v == item)),
+            (Value::Str(s), Value::Str(item)) => Some(s.contains(item.as_str())),
+            _ => None,
+        }
+    }
+
+    fn as_numeric(&self) -> Option<Numeric> {
+        match self {
+            Value::Bool(b) => Some(Numeric::Int(*b as i64)),
+            Value::I64(n) => Some(Numeric::Int(*n)),
+            Value::F64(n) => Some(Numeric::Float(*n)),
+            Value::Str(_) | Value::Tuple(_) | Value::Module(_) => None,
+        }
+    }
+
+    pub fn as_module(&self) -> MLResult<&'static str> {
         if let Value::Module(m) = self {
-            Ok(m)
+            Ok(*m)
         } else {
             Err(MathlineError::CannotUseAsModule)
         }
     }
 }
 
+/// Python's equality relation: bools compare as the integers 0 and 1,
+/// ints and floats compare by exact value, and NaN equals nothing
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Str(l), Value::Str(r)) => l == r,
+            (Value::Tuple(l), Value::Tuple(r)) => l == r,
+            (Value::Module(l), Value::Module(r)) => l == r,
+            _ => self.partial_cmp(other) == Some(Ordering::Equal),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.as_numeric()?, other.as_numeric()?) {
+            (Numeric::Int(l), Numeric::Int(r)) => Some(l.cmp(&r)),
+            (Numeric::Float(l), Numeric::Float(r)) => l.partial_cmp(&r),
+            (Numeric::Int(l), Numeric::Float(r)) => cmp_int_float(l, r),
+            (Numeric::Float(l), Numeric::Int(r)) => cmp_int_float(r, l).map(Ordering::reverse),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Numeric {
+    Int(i64),
+    Float(f64),
+}
+
+/// Compares an int against a float without rounding the int,
+/// so that `2**53 + 1 == 2.0**53` is false
+fn cmp_int_float(int: i64, float: f64) -> Option<Ordering> {
+    // i64::MAX rounds up to this value when converted to f64
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if float.is_nan() {
+        return None;
+    }
+    if float >= LIMIT {
+        return Some(Ordering::Less);
+    }
+    if float < -LIMIT {
+        return Some(Ordering::Greater);
+    }
+    let truncated = float.trunc();
+    match int.cmp(&(truncated as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(float - truncated)),
+        ordering => Some(ordering),
+    }
+}
+
 impl From<Value> for Expression {
     fn from(value: Value) -> Self {
         Expression::Value(match value {
+            Value::Tuple(values) => {
+                return Expression::Tuple(values.into_iter().map(Expression::from).collect());
+            }
             Value::Bool(b) => ValueExpression::Bool(b),
             Value::I64(n) => ValueExpression::I64(n),
             Value::F64(n) => ValueExpression::F64(n),
+            Value::Str(s) => ValueExpression::Str(s),
             Value::Module(m) => ValueExpression::Variable(m.to_string()),
         })
     }
@@ -56,7 +172,77 @@ impl Display for Value {
             Value::Bool(v) => v.fmt(f),
             Value::I64(v) => v.fmt(f),
             Value::F64(v) => write!(f, "{v:.5}"),
+            Value::Str(v) => v.fmt(f),
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                if values.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
             Value::Module(v) => v.fmt(f),
         }
     }
 }
+
+impl From<&Value> for serde_json::Value {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Bool(b) => (*b).into(),
+            Value::I64(n) => (*n).into(),
+            // JSON has no infinities or NaN, so those are written as text
+            Value::F64(n) => serde_json::Number::from_f64(*n)
+                .map_or_else(|| value.to_string().into(), Into::into),
+            Value::Str(s) => s.as_str().into(),
+            Value::Tuple(values) => values.iter().map(serde_json::Value::from).collect(),
+            Value::Module(m) => (*m).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_like_python() {
+        let nan = Value::F64(f64::NAN);
+        assert!(Value::I64(1) == Value::F64(1.0));
+        assert!(Value::Bool(true) == Value::I64(1));
+        // 2**53 + 1 has no float, so it mustn't round to equal one
+        assert!(Value::I64(9_007_199_254_740_993) != Value::F64(9_007_199_254_740_992.0));
+        assert!(Value::I64(9_007_199_254_740_993) > Value::F64(9_007_199_254_740_992.0));
+        assert!(Value::F64(0.5) < Value::I64(1));
+        assert!(nan != nan);
+        assert_eq!(nan.partial_cmp(&Value::I64(1)), None);
+        assert!(Value::Str("1".to_string()) != Value::I64(1));
+    }
+
+    #[test]
+    fn is_compares_identity() {
+        assert!(!Value::I64(1).is(&Value::F64(1.0)));
+        assert!(Value::F64(f64::NAN).is(&Value::F64(f64::NAN)));
+        assert!(!Value::F64(0.0).is(&Value::F64(-0.0)));
+        let tuple = Value::Tuple(vec![Value::I64(1), Value::F64(f64::NAN)]);
+        assert!(tuple.is(&tuple.clone()));
+    }
+
+    #[test]
+    fn contains_like_python() {
+        let nan = Value::F64(f64::NAN);
+        let tuple = Value::Tuple(vec![Value::I64(1), nan.clone()]);
+        assert_eq!(tuple.contains(&Value::F64(1.0)), Some(true));
+        assert_eq!(tuple.contains(&nan), Some(true));
+        assert_eq!(tuple.contains(&Value::I64(2)), Some(false));
+        let s = Value::Str("mathline".to_string());
+        assert_eq!(s.contains(&Value::Str("line".to_string())), Some(true));
+        assert_eq!(s.contains(&Value::I64(1)), None);
+        assert_eq!(Value::I64(1).contains(&Value::I64(1)), None);
+    }
+}
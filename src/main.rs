@@ -1,26 +1,131 @@
+use std::{io::Write, ops::ControlFlow, path::PathBuf, sync::Arc, time::Duration};
+
 use argh::FromArgs;
-use fallible_iterator::FallibleIterator;
 
 use crate::{
-    agent::call_agent, error::MathlineError, interpreter::evaluator::Evaluator, parser::Parser,
+    agent::{
+        Conversation, Provider, StreamingAgentClient, ToolAgentClient,
+        cache::CachingClient,
+        call_agent, explain,
+        mock::{MockAgentClient, RecordingClient},
+        stream_agent,
+        template::PromptTemplate,
+        tools, verify,
+    },
+    error::MathlineError,
+    interpreter::evaluator::Evaluator,
+    parser::{Notation, ParseOptions},
+    render::Typeset,
 };
 
 mod agent;
+mod batch;
+mod dirs;
 mod error;
+mod eval;
 mod interpreter;
+mod mcp;
 mod parser;
+mod render;
+mod report;
+mod serve;
+mod vote;
 
 pub type MLResult<T> = std::result::Result<T, MathlineError>;
 
 #[derive(FromArgs)]
 /// Natural language solver for mathematical expressions
 struct Args {
-    /// ollama model ID (default is "gemma3:4b")
+    /// model ID (default is "gemma3:4b" for ollama and "gpt-4o-mini" for openai)
     #[argh(option)]
     model: Option<String>,
-    /// natural language request
+    /// LLM provider: ollama or openai (default is ollama)
+    #[argh(option, default = "Provider::Ollama")]
+    provider: Provider,
+    /// expression notation: python, math, latex or auto (default is auto)
+    #[argh(option, default = "Notation::Auto")]
+    notation: Notation,
+    /// treat ^ as bitwise xor rather than exponentiation
+    #[argh(switch)]
+    xor: bool,
+    /// typeset parse and step lines as text, latex or mathml (default is text)
+    #[argh(option, default = "Typeset::Text")]
+    typeset: Typeset,
+    /// ask the model this many times at once and report the answer most agree on
+    #[argh(option, default = "1")]
+    samples: usize,
+    /// show the model's response as it arrives
+    #[argh(switch)]
+    stream: bool,
+    /// prompt template: default, word-problems, latex, a name under ~/.config/mathline/prompts,
+    /// or the path to a TOML file (default is default)
+    #[argh(option, default = "String::from(\"default\")")]
+    prompt: String,
+    /// ask the model to explain the answer, using the evaluated value
+    #[argh(switch)]
+    explain: bool,
+    /// ask the model to describe the expression back in words and check it matches the request
+    #[argh(switch)]
+    verify: bool,
+    /// with --verify, translate the request once more when the expression doesn't match
+    #[argh(switch)]
+    retry: bool,
+    /// let the model call the evaluator as often as it needs, then answer in words
+    #[argh(switch)]
+    tools: bool,
+    /// keep asking follow-up requests from stdin, with the earlier ones as context
+    #[argh(switch)]
+    chat: bool,
+    /// answer each line of this file, or of stdin if it is -, instead of a single request
+    #[argh(option)]
+    batch: Option<String>,
+    /// how many batch questions to ask the model at once (default is 4)
+    #[argh(option, default = "4")]
+    concurrency: usize,
+    /// batch output format: text, jsonl or csv (default is text)
+    #[argh(option, default = "batch::Format::Text")]
+    format: batch::Format,
+    /// always ask the model, rather than reusing a cached response
+    #[argh(switch)]
+    no_cache: bool,
+    /// how many seconds cached responses are reused for (default is a week)
+    #[argh(option, default = "604800")]
+    cache_ttl: u64,
+    /// write each model response to a fixture file in this directory
+    #[argh(option)]
+    record: Option<PathBuf>,
+    /// answer with the fixtures recorded in this directory instead of calling the model
+    #[argh(option)]
+    replay: Option<PathBuf>,
+    /// natural language request (starts a chat if left out)
     #[argh(positional)]
-    request: String,
+    request: Option<String>,
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Eval(EvalArgs),
+    Cache(CacheArgs),
+    Serve(ServeArgs),
+    Mcp(McpArgs),
+}
+
+#[derive(FromArgs)]
+/// Measure how often models answer a dataset of questions correctly
+#[argh(subcommand, name = "eval")]
+struct EvalArgs {
+    /// JSONL file with a question, expected answer and optional tolerance on each line
+    #[argh(positional)]
+    dataset: PathBuf,
+    /// model to evaluate, can be repeated (default is the model of the main options)
+    #[argh(option, long = "model")]
+    models: Vec<String>,
+    /// file to write each answer to as JSONL (default is the dataset with .results.jsonl)
+    #[argh(option)]
+    output: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -32,27 +137,402 @@ async fn main() {
 
 async fn main_inner() -> MLResult<()> {
     let args: Args = argh::from_env();
-    let model = args
-        .model.as_deref()
-        .unwrap_or("gemma3:4b");
+    match &args.command {
+        Some(Command::Eval(eval_args)) => return evaluate(&args, eval_args).await,
+        Some(Command::Cache(CacheArgs {
+            command: CacheCommand::Clear(_),
+        })) => return clear_cache(),
+        _ => {}
+    }
+    if args.tools {
+        let client = args.provider.tool_client(args.model())?;
+        let mut conversation = tools::conversation();
+        return answer_requests(&args, async |request: &str| {
+            solve_with_tools(&args, client.as_ref(), &mut conversation, request).await
+        })
+        .await;
+    }
+    let template = PromptTemplate::load(&args.prompt)?.for_model(args.model());
+    let mut conversation = Conversation::new(&template)?;
+    let client = args.client(args.model())?;
+
+    if let Some(Command::Serve(serve_args)) = &args.command {
+        let server = serve::Server::new(
+            client,
+            conversation,
+            args.parse_options(),
+            serve_args.max_concurrent,
+        );
+        return Arc::new(server)
+            .listen(&serve_args.host, serve_args.port)
+            .await;
+    }
+    if let Some(Command::Mcp(_)) = &args.command {
+        let server = mcp::McpServer::new(client, conversation, args.parse_options());
+        return server.run().await;
+    }
+
+    if let Some(path) = &args.batch {
+        return answer_batch(&args, client, &conversation, path).await;
+    }
+
+    answer_requests(&args, async |request: &str| {
+        solve(&args, &client, &mut conversation, request).await
+    })
+    .await
+}
+
+/// Answers the request on the command line, then follow-ups from stdin when chatting
+async fn answer_requests(
+    args: &Args,
+    mut solve: impl AsyncFnMut(&str) -> MLResult<()>,
+) -> MLResult<()> {
+    if let Some(request) = &args.request {
+        solve(request).await?;
+        if !args.chat {
+            return Ok(());
+        }
+    }
+
+    // each follow-up sees the earlier requests and answers
+    let stdin = std::io::stdin();
+    loop {
+        eprint!("> ");
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let request = line.trim();
+        if request.is_empty() {
+            continue;
+        }
+        if let Err(error) = solve(request).await {
+            eprintln!("ERROR: {error}");
+        }
+    }
+}
+
+#[derive(FromArgs)]
+/// Answer requests over HTTP: POST /solve, POST /evaluate and GET /functions
+#[argh(subcommand, name = "serve")]
+struct ServeArgs {
+    /// port to listen on (default is 8080)
+    #[argh(option, default = "8080")]
+    port: u16,
+    /// address to listen on (default is 127.0.0.1)
+    #[argh(option, default = "String::from(\"127.0.0.1\")")]
+    host: String,
+    /// how many questions to ask the model at once (default is 4)
+    #[argh(option, default = "4")]
+    max_concurrent: usize,
+}
+
+#[derive(FromArgs)]
+/// Run a Model Context Protocol server on stdio, with evaluate_expression and
+/// solve_word_problem tools
+#[argh(subcommand, name = "mcp")]
+struct McpArgs {}
+
+#[derive(FromArgs)]
+/// Manage the cache of model responses
+#[argh(subcommand, name = "cache")]
+struct CacheArgs {
+    #[argh(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum CacheCommand {
+    Clear(ClearArgs),
+}
+
+#[derive(FromArgs)]
+/// Remove every cached response
+#[argh(subcommand, name = "clear")]
+struct ClearArgs {}
+
+impl Args {
+    fn model(&self) -> &str {
+        self.model
+            .as_deref()
+            .unwrap_or(self.provider.default_model())
+    }
+
+    /// Returns the client for the model, caching, recording or replaying its responses
+    /// as asked to
+    fn client(&self, model: &str) -> MLResult<Arc<dyn StreamingAgentClient>> {
+        if let Some(dir) = &self.replay {
+            return Ok(Arc::new(MockAgentClient::replay(dir)?));
+        }
+        let mut client = self.provider.client(model)?;
+        // cached samples would all be the same, so there would be nothing to vote on
+        if !self.no_cache
+            && self.samples == 1
+            && let Some(dir) = CachingClient::default_dir()
+        {
+            let ttl = Duration::from_secs(self.cache_ttl);
+            client = Arc::new(CachingClient::new(
+                client,
+                self.provider,
+                model.to_string(),
+                ttl,
+                dir,
+            ));
+        }
+        Ok(match &self.record {
+            Some(dir) => Arc::new(RecordingClient::new(client, dir.clone())),
+            None => client,
+        })
+    }
+
+    fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            caret_is_xor: self.xor,
+            notation: self.notation,
+        }
+    }
+}
+
+/// Answers one request, and adds it and its answer to the conversation
+async fn solve(
+    args: &Args,
+    client: &Arc<dyn StreamingAgentClient>,
+    conversation: &mut Conversation,
+    request: &str,
+) -> MLResult<()> {
+    let options = args.parse_options();
+    let Some(mut answer) = translate(args, client, conversation, request).await? else {
+        return Ok(());
+    };
+
+    if args.verify {
+        let verdict = verify::verify(client.as_ref(), request, &answer, options).await?;
+        print_verdict(&verdict);
+        if !verdict.matches && args.retry {
+            let retry = verdict.retry_request(request);
+            let Some(retried) = translate(args, client, conversation, &retry).await? else {
+                return Ok(());
+            };
+            answer = retried;
+            print_verdict(&verify::verify(client.as_ref(), request, &answer, options).await?);
+        }
+    }
+
+    if args.explain {
+        let report = report::Report::answer(Some(request), answer, options);
+        let explanation = explain::explain(client.as_ref(), &report).await?;
+        println!("Explanation: {explanation}");
+    }
+    Ok(())
+}
+
+/// Asks the model to translate the request, printing the expression and its evaluation.
+/// Returns the answer if it evaluated, after adding it to the conversation.
+async fn translate(
+    args: &Args,
+    client: &Arc<dyn StreamingAgentClient>,
+    conversation: &mut Conversation,
+    request: &str,
+) -> MLResult<Option<agent::Answer>> {
+    let options = args.parse_options();
+    conversation.ask(request);
 
-    let expression_string = match call_agent(&args.request, model).await {
-        Ok(response) => response,
+    if args.samples > 1 {
+        let samples = vote::sample(client.clone(), conversation, options, args.samples);
+        return print_vote(conversation, samples.await).map(|_| None);
+    }
+
+    let answer = if args.stream {
+        let mut on_token = |token: &str| {
+            eprint!("{token}");
+            ControlFlow::Continue(())
+        };
+        let answer = stream_agent(client.as_ref(), conversation, &mut on_token).await;
+        eprintln!();
+        answer
+    } else {
+        call_agent(client.as_ref(), conversation).await
+    };
+    let answer = match answer {
+        Ok(answer) => answer,
         Err(error) => {
             eprintln!("{error}");
-            return Ok(());
+            return Ok(None);
         }
     };
 
-    println!("LLM: {expression_string}");
+    println!("LLM: {}", answer.expression);
+    for (name, value) in &answer.variables {
+        println!("Variable: {name} = {value}");
+    }
+    // with --explain, the second pass's explanation is printed after the answer
+    if !args.explain
+        && let Some(explanation) = &answer.explanation
+    {
+        println!("Explanation: {explanation}");
+    }
 
-    let expressions: Vec<_> = Parser::new(&expression_string).collect()?;
+    let expressions = match parser::parse(&answer.expression, options) {
+        Ok(expressions) => expressions,
+        Err(error) => {
+            conversation.answered(&answer, None);
+            return Err(error);
+        }
+    };
 
+    let mut last_value = None;
     for expr in expressions {
-        println!("Parse: {expr}");
-        let evaluator = Evaluator::new(expr);
-        let value = evaluator.eval()?;
+        let expr = answer.substitute(expr);
+        println!("Parse: {}", render::expression(&expr, args.typeset));
+        let mut evaluator = Evaluator::new(expr);
+        let result = evaluator.eval();
+        for step in evaluator.steps() {
+            println!("Step: {}", render::step(step, args.typeset));
+        }
+        let value = match result {
+            Ok(value) => value,
+            Err(error) => {
+                conversation.answered(&answer, None);
+                return Err(error);
+            }
+        };
         println!("Answer: {value}");
+        last_value = Some(value);
+    }
+    conversation.answered(&answer, last_value.as_ref());
+    Ok(Some(answer))
+}
+
+fn print_verdict(verdict: &verify::Verdict) {
+    println!("Described: {}", verdict.description);
+    if verdict.matches {
+        println!("Verified: the expression matches the request");
+    } else {
+        let critique = verdict.critique.as_deref().unwrap_or_default();
+        println!("Mismatch: {critique}");
+    }
+}
+
+fn print_vote(conversation: &mut Conversation, samples: Vec<vote::Sample>) -> MLResult<()> {
+    for sample in &samples {
+        match &sample.result {
+            Ok(value) => println!("Sample: {} => {value}", sample.expression()),
+            Err(error) => println!("Sample: {} => ERROR: {error}", sample.expression()),
+        }
+    }
+    let vote = vote::Vote::tally(samples);
+    let Some(majority) = &vote.majority else {
+        // no sample evaluated, so report the first failure
+        let sample = vote.samples.into_iter().next();
+        return sample.map_or(Ok(()), |sample| sample.result.map(|_| ()));
+    };
+    println!("Answer: {majority}");
+    println!(
+        "Agreement: {}/{} ({:.0}%)",
+        vote.agreeing,
+        vote.samples.len(),
+        vote.agreement() * 100.0
+    );
+    for sample in vote.dissenting() {
+        match &sample.result {
+            Ok(value) => println!("Dissent: {} => {value}", sample.expression()),
+            Err(error) => println!("Dissent: {} => ERROR: {error}", sample.expression()),
+        }
+    }
+    if let Some(answer) = vote.majority_answer() {
+        conversation.answered(answer, Some(majority));
+    }
+    Ok(())
+}
+
+/// Lets the model work the request out by calling the evaluator, printing each evaluation
+/// and the model's final answer
+async fn solve_with_tools(
+    args: &Args,
+    client: &dyn ToolAgentClient,
+    conversation: &mut Conversation,
+    request: &str,
+) -> MLResult<()> {
+    conversation.ask(request);
+    let print = |report: &report::Report| match (&report.value, &report.error) {
+        (Some(value), _) => println!("Tool: {} => {value}", report.expression),
+        (None, error) => println!(
+            "Tool: {} => ERROR: {}",
+            report.expression,
+            error.as_deref().unwrap_or_default()
+        ),
+    };
+    let answer = tools::solve(client, conversation, args.parse_options(), print).await?;
+    println!("Answer: {answer}");
+    Ok(())
+}
+
+/// Answers every question in the batch, printing one result per line
+async fn answer_batch(
+    args: &Args,
+    client: Arc<dyn StreamingAgentClient>,
+    conversation: &Conversation,
+    path: &str,
+) -> MLResult<()> {
+    let questions = batch::read_questions(path)?;
+    let mut stdout = std::io::stdout().lock();
+    if let Some(header) = batch::header(args.format) {
+        writeln!(stdout, "{header}")?;
+    }
+    let print = |result: batch::BatchResult| result.write(args.format, &mut stdout);
+    batch::run(
+        questions,
+        conversation,
+        client,
+        args.parse_options(),
+        args.concurrency,
+        args.verify,
+        print,
+    )
+    .await
+}
+
+/// Removes every cached response
+fn clear_cache() -> MLResult<()> {
+    let Some(dir) = CachingClient::default_dir() else {
+        return Ok(());
+    };
+    let count = CachingClient::clear(&dir)?;
+    println!("Removed {count} cached responses from {}", dir.display());
+    Ok(())
+}
+
+/// Runs each model over the dataset, printing a report for each and writing every answer
+async fn evaluate(args: &Args, eval_args: &EvalArgs) -> MLResult<()> {
+    let items = eval::read_dataset(&eval_args.dataset)?;
+    let template = PromptTemplate::load(&args.prompt)?;
+    let output = eval_args
+        .output
+        .clone()
+        .unwrap_or_else(|| eval_args.dataset.with_extension("results.jsonl"));
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&output)?);
+
+    let models = match eval_args.models.as_slice() {
+        [] => vec![args.model().to_string()],
+        models => models.to_vec(),
+    };
+    for model in models {
+        let client = args.client(&model)?;
+        let report = eval::run(
+            &items,
+            &template,
+            client.as_ref(),
+            &model,
+            args.parse_options(),
+        )
+        .await?;
+        for result in &report.results {
+            writeln!(file, "{}", serde_json::to_string(result)?)?;
+        }
+        println!("{report}");
     }
+    file.flush()?;
+    println!("Results: {}", output.display());
     Ok(())
 }
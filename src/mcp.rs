@@ -0,0 +1,315 @@
+//! A Model Context Protocol server, so other agents can use the evaluator as a tool.
+//! Messages are JSON-RPC, one per line on stdin and stdout.
+
+use std::sync::{Arc, LazyLock};
+
+use serde::Deserialize;
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::{
+    MLResult,
+    agent::{AgentClient, Conversation, call_agent},
+    parser::{Notation, ParseOptions},
+    report::Report,
+};
+
+static PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// The JSON schema of a [Report], which both tools return
+static REPORT_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    json!({
+        "type": "object",
+        "properties": {
+            "question": { "type": "string" },
+            "expression": { "type": "string" },
+            "variables": {
+                "type": "object",
+                "additionalProperties": { "type": "number" }
+            },
+            "explanation": { "type": "string" },
+            "steps": {
+                "type": "array",
+                "description": "Each intermediate result, innermost first",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "expression": { "type": "string" },
+                        "value": {}
+                    },
+                    "required": ["expression", "value"]
+                }
+            },
+            "value": { "description": "The result, or null if evaluating failed" },
+            "error": { "type": "string" }
+        },
+        "required": ["expression", "steps", "value"]
+    })
+});
+
+static TOOLS: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    json!([
+        {
+            "name": "evaluate_expression",
+            "description": "Evaluates a mathematical expression exactly, step by step. \
+                Supports arithmetic, comparisons, bitwise and boolean operators, \
+                and functions like sqrt, log, sin and factorial.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "expression": {
+                        "type": "string",
+                        "description": "The expression, like `sqrt(16) + 2 ** 10`"
+                    },
+                    "notation": {
+                        "type": "string",
+                        "enum": ["python", "math", "latex", "auto"],
+                        "description": "How the expression is written (default is auto)"
+                    }
+                },
+                "required": ["expression"]
+            },
+            "outputSchema": *REPORT_SCHEMA
+        },
+        {
+            "name": "solve_word_problem",
+            "description": "Translates a question in plain language into an expression \
+                and evaluates it step by step.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "question": {
+                        "type": "string",
+                        "description": "The question, like `what is 15% of 80?`"
+                    }
+                },
+                "required": ["question"]
+            },
+            "outputSchema": *REPORT_SCHEMA
+        }
+    ])
+});
+
+/// A JSON-RPC request, or a notification if it has no id
+#[derive(Deserialize)]
+struct RpcRequest {
+    id: Option<serde_json::Value>,
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct EvaluateArguments {
+    expression: String,
+    notation: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SolveArguments {
+    question: String,
+}
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub struct McpServer {
+    client: Arc<dyn AgentClient>,
+    /// The system prompt and examples every question starts from
+    conversation: Conversation,
+    options: ParseOptions,
+}
+
+impl McpServer {
+    pub fn new(
+        client: Arc<dyn AgentClient>,
+        conversation: Conversation,
+        options: ParseOptions,
+    ) -> Self {
+        Self {
+            client,
+            conversation,
+            options,
+        }
+    }
+
+    /// Answers messages from stdin until it closes
+    pub async fn run(&self) -> MLResult<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line).await {
+                stdout.write_all(format!("{response}\n").as_bytes()).await?;
+                stdout.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers one message, or returns None for a notification
+    async fn handle(&self, message: &str) -> Option<serde_json::Value> {
+        let request: RpcRequest = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(error) => return Some(error_response(json!(null), PARSE_ERROR, error)),
+        };
+        let id = request.id?;
+        let result = match request.method.as_str() {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "mathline", "version": env!("CARGO_PKG_VERSION") }
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": *TOOLS })),
+            "tools/call" => self.call_tool(request.params).await,
+            method => Err((METHOD_NOT_FOUND, format!("unknown method {method}"))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, message),
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, (i64, String)> {
+        let invalid = |error: serde_json::Error| (INVALID_PARAMS, error.to_string());
+        let call: ToolCall = serde_json::from_value(params).map_err(invalid)?;
+        let report = match call.name.as_str() {
+            "evaluate_expression" => {
+                let arguments: EvaluateArguments =
+                    serde_json::from_value(call.arguments).map_err(invalid)?;
+                let mut options = self.options;
+                if let Some(notation) = arguments.notation {
+                    options.notation = notation
+                        .parse::<Notation>()
+                        .map_err(|error| (INVALID_PARAMS, error))?;
+                }
+                Report::evaluate_isolated(arguments.expression, options).await
+            }
+            "solve_word_problem" => {
+                let arguments: SolveArguments =
+                    serde_json::from_value(call.arguments).map_err(invalid)?;
+                let mut conversation = self.conversation.clone();
+                conversation.ask(&arguments.question);
+                match call_agent(self.client.as_ref(), &conversation).await {
+                    Ok(answer) => {
+                        Report::answer_isolated(Some(arguments.question), answer, self.options)
+                            .await
+                    }
+                    Err(error) => return Ok(tool_error(&error.to_string())),
+                }
+            }
+            name => return Err((INVALID_PARAMS, format!("unknown tool {name}"))),
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": json!(report).to_string() }],
+            "structuredContent": report,
+            "isError": report.error.is_some()
+        }))
+    }
+}
+
+/// A tool result for a failure the calling agent may be able to work around
+fn tool_error(message: &str) -> serde_json::Value {
+    json!({
+        "content": [{ "type": "text", "text": message }],
+        "isError": true
+    })
+}
+
+fn error_response(id: serde_json::Value, code: i64, message: impl ToString) -> serde_json::Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.to_string() }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{mock::MockAgentClient, template::PromptTemplate};
+
+    #[tokio::test]
+    async fn calls_tools() {
+        let client = MockAgentClient::new([(
+            "what is two to the tenth?",
+            r#"{"expression": "2 ** 10", "variables": {}}"#,
+        )]);
+        let conversation = Conversation::new(&PromptTemplate::default()).unwrap();
+        let server = McpServer::new(Arc::new(client), conversation, ParseOptions::default());
+
+        let response = server
+            .handle(r#"{"jsonrpc": "2.0", "id": 1, "method": "tools/list"}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            response["result"]["tools"][0]["name"],
+            "evaluate_expression"
+        );
+
+        let response = server
+            .handle(
+                r#"{"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "evaluate_expression", "arguments": {"expression": "\\sqrt{16} + 1", "notation": "latex"}}}"#,
+            )
+            .await
+            .unwrap();
+        let report = &response["result"]["structuredContent"];
+        assert_eq!(report["value"], json!(5.0));
+        assert_eq!(report["steps"].as_array().unwrap().len(), 2);
+        assert_eq!(response["result"]["isError"], false);
+
+        let response = server
+            .handle(
+                r#"{"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "solve_word_problem", "arguments": {"question": "what is two to the tenth?"}}}"#,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response["id"], 3);
+        assert_eq!(response["result"]["structuredContent"]["value"], 1024);
+
+        // a bad expression is an error result, and the server keeps answering
+        let expressions = [
+            ("1 % 0".to_string(), "python"),
+            ("(".repeat(30_000), "python"),
+            ("\\sum_{i=1}^{100000} i^4".to_string(), "latex"),
+        ];
+        for (expression, notation) in expressions {
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": 5,
+                "method": "tools/call",
+                "params": { "name": "evaluate_expression", "arguments": { "expression": expression, "notation": notation } }
+            });
+            let response = server.handle(&request.to_string()).await.unwrap();
+            assert_eq!(
+                response["result"]["isError"], true,
+                "evaluating {expression}"
+            );
+            let error = &response["result"]["structuredContent"]["error"];
+            assert!(!error.as_str().unwrap().contains("invalid"), "{error}");
+        }
+
+        let notification = r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#;
+        assert!(server.handle(notification).await.is_none());
+        let response = server
+            .handle(r#"{"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {"name": "nope"}}"#)
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+}
@@ -2,39 +2,45 @@ use std::fmt::Display;
 
 use crate::{error::MathlineError, parser::token::Op};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Value(ValueExpression),
     Fn(FnExpression),
     Infix(InfixExpression),
     Prefix(PrefixExpression),
+    Comparison(ComparisonExpression),
+    Tuple(Vec<Expression>),
+    Series(SeriesExpression),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ValueExpression {
     Bool(bool),
     I64(i64),
     F64(f64),
+    Str(String),
     Variable(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FnExpression {
     pub name: String,
     pub parameters: Vec<Expression>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PrefixExpression {
     pub op: PrefixOp,
     pub rhs: Box<Expression>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PrefixOp {
     Not,
     Plus,
     Minus,
+    PlusMinus,
+    BitNot,
 }
 
 impl TryFrom<Op> for PrefixOp {
@@ -45,26 +51,59 @@ impl TryFrom<Op> for PrefixOp {
             Op::Not => Ok(Self::Not),
             Op::Plus => Ok(Self::Plus),
             Op::Minus => Ok(Self::Minus),
+            Op::PlusMinus => Ok(Self::PlusMinus),
+            Op::BitNot => Ok(Self::BitNot),
             _ => Err(MathlineError::InvalidSyntax),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InfixExpression {
     pub op: InfixOp,
     pub lhs: Box<Expression>,
     pub rhs: Box<Expression>,
 }
 
-#[derive(Debug)]
+/// A chain of comparisons such as `a < b <= c`, which is
+/// equivalent to `a < b and b <= c` except that `b` is only evaluated once
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonExpression {
+    pub lhs: Box<Expression>,
+    pub comparisons: Vec<(InfixOp, Expression)>,
+}
+
+/// A sum or product of `body` as `variable` counts from `lower` to `upper` inclusive,
+/// like LaTeX's `\sum_{i=1}^{n} i^2`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeriesExpression {
+    pub op: SeriesOp,
+    pub variable: String,
+    pub lower: Box<Expression>,
+    pub upper: Box<Expression>,
+    pub body: Box<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SeriesOp {
+    Sum,
+    Product,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum InfixOp {
     Add,
     Subtract,
+    PlusMinus,
     Multiply,
     Divide,
     Modulo,
     Exponent,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     And,
     Or,
     Equal,
@@ -73,6 +112,10 @@ pub enum InfixOp {
     LessThanOrEqual,
     GreaterThan,
     GreaterThanOrEqual,
+    Is,
+    IsNot,
+    In,
+    NotIn,
     Dot,
 }
 
@@ -83,10 +126,16 @@ impl TryFrom<Op> for InfixOp {
         Ok(match op {
             Op::Plus => Self::Add,
             Op::Minus => Self::Subtract,
+            Op::PlusMinus => Self::PlusMinus,
             Op::Multiply => Self::Multiply,
             Op::Divide => Self::Divide,
             Op::Modulo => Self::Modulo,
             Op::Exponent => Self::Exponent,
+            Op::BitAnd => Self::BitAnd,
+            Op::BitOr => Self::BitOr,
+            Op::BitXor => Self::BitXor,
+            Op::ShiftLeft => Self::ShiftLeft,
+            Op::ShiftRight => Self::ShiftRight,
             Op::And => Self::And,
             Op::Or => Self::Or,
             Op::Equal => Self::Equal,
@@ -95,12 +144,93 @@ impl TryFrom<Op> for InfixOp {
             Op::LessThanOrEqual => Self::LessThanOrEqual,
             Op::GreaterThan => Self::GreaterThan,
             Op::GreaterThanOrEqual => Self::GreaterThanOrEqual,
+            Op::Is => Self::Is,
+            Op::In => Self::In,
+            Op::NotIn => Self::NotIn,
             Op::Dot => Self::Dot,
-            Op::Not => return Err(MathlineError::InvalidSyntax),
+            Op::Not | Op::BitNot | Op::Sqrt | Op::Cbrt => return Err(MathlineError::InvalidSyntax),
         })
     }
 }
 
+impl Expression {
+    /// Returns a copy of the expression with every use of the variable replaced
+    pub fn substitute(&self, variable: &str, value: &Expression) -> Expression {
+        let sub = |expr: &Expression| Box::new(expr.substitute(variable, value));
+        match self {
+            Expression::Value(ValueExpression::Variable(v)) if v == variable => value.clone(),
+            Expression::Value(_) => self.clone(),
+            Expression::Fn(expr) => Expression::Fn(FnExpression {
+                name: expr.name.clone(),
+                parameters: expr
+                    .parameters
+                    .iter()
+                    .map(|p| p.substitute(variable, value))
+                    .collect(),
+            }),
+            Expression::Infix(expr) => Expression::Infix(InfixExpression {
+                op: expr.op.clone(),
+                lhs: sub(&expr.lhs),
+                rhs: sub(&expr.rhs),
+            }),
+            Expression::Prefix(expr) => Expression::Prefix(PrefixExpression {
+                op: expr.op.clone(),
+                rhs: sub(&expr.rhs),
+            }),
+            Expression::Comparison(expr) => Expression::Comparison(ComparisonExpression {
+                lhs: sub(&expr.lhs),
+                comparisons: expr
+                    .comparisons
+                    .iter()
+                    .map(|(op, rhs)| (op.clone(), rhs.substitute(variable, value)))
+                    .collect(),
+            }),
+            Expression::Tuple(exprs) => Expression::Tuple(
+                exprs
+                    .iter()
+                    .map(|e| e.substitute(variable, value))
+                    .collect(),
+            ),
+            Expression::Series(expr) => Expression::Series(SeriesExpression {
+                op: expr.op.clone(),
+                variable: expr.variable.clone(),
+                lower: sub(&expr.lower),
+                upper: sub(&expr.upper),
+                // the series' own variable shadows the outer one
+                body: if expr.variable == variable {
+                    expr.body.clone()
+                } else {
+                    sub(&expr.body)
+                },
+            }),
+        }
+    }
+}
+
+impl Expression {
+    /// Returns true if the expression nests more than `depth` levels deep.
+    /// It only looks that far down, so it is safe on an expression of any depth.
+    pub fn is_deeper_than(&self, depth: usize) -> bool {
+        if depth == 0 {
+            return true;
+        }
+        let deeper = |expr: &Expression| expr.is_deeper_than(depth - 1);
+        match self {
+            Expression::Value(_) => false,
+            Expression::Fn(expr) => expr.parameters.iter().any(deeper),
+            Expression::Infix(expr) => deeper(&expr.lhs) || deeper(&expr.rhs),
+            Expression::Prefix(expr) => deeper(&expr.rhs),
+            Expression::Comparison(expr) => {
+                deeper(&expr.lhs) || expr.comparisons.iter().any(|(_, rhs)| deeper(rhs))
+            }
+            Expression::Tuple(exprs) => exprs.iter().any(deeper),
+            Expression::Series(expr) => {
+                deeper(&expr.lower) || deeper(&expr.upper) || deeper(&expr.body)
+            }
+        }
+    }
+}
+
 impl Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -108,6 +238,22 @@ impl Display for Expression {
             Expression::Fn(expr) => expr.fmt(f),
             Expression::Infix(expr) => expr.fmt(f),
             Expression::Prefix(expr) => expr.fmt(f),
+            Expression::Comparison(expr) => expr.fmt(f),
+            Expression::Tuple(exprs) => {
+                write!(f, "(")?;
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{expr}")?;
+                }
+                // a tuple of one needs a trailing comma, as in python
+                if exprs.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Expression::Series(expr) => expr.fmt(f),
         }
     }
 }
@@ -115,9 +261,23 @@ impl Display for Expression {
 impl Display for ValueExpression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ValueExpression::Bool(bool) => bool.fmt(f),
+            ValueExpression::Bool(true) => write!(f, "True"),
+            ValueExpression::Bool(false) => write!(f, "False"),
             ValueExpression::I64(num) => num.fmt(f),
-            ValueExpression::F64(num) => num.fmt(f),
+            // debug formatting keeps the point, so `1.0` doesn't read back as an int
+            ValueExpression::F64(num) => write!(f, "{num:?}"),
+            ValueExpression::Str(s) => {
+                write!(f, "'")?;
+                for c in s.chars() {
+                    match c {
+                        '\\' | '\'' => write!(f, "\\{c}")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                write!(f, "'")
+            }
             ValueExpression::Variable(v) => v.fmt(f),
         }
     }
@@ -127,25 +287,78 @@ impl Display for FnExpression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.name.fmt(f)?;
         write!(f, "(")?;
-        if !self.parameters.is_empty() {
-            write!(f, "{}", &self.parameters[0])?;
-        }
-        for p in &self.parameters[1..] {
-            write!(f, ", {p}")?;
+        for (i, p) in self.parameters.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{p}")?;
         }
         write!(f, ")")
     }
 }
 
+/// Writes an operand, with parens if it would otherwise parse differently
+fn write_operand(
+    f: &mut std::fmt::Formatter<'_>,
+    expr: &Expression,
+    needs_parens: bool,
+) -> std::fmt::Result {
+    if needs_parens {
+        write!(f, "({expr})")
+    } else {
+        write!(f, "{expr}")
+    }
+}
+
 impl Display for PrefixExpression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{}", self.op, self.rhs)
+        self.op.fmt(f)?;
+        write_operand(f, &self.rhs, self.op.rhs_needs_parens(&self.rhs))
     }
 }
 
 impl Display for InfixExpression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {} {}", self.lhs, self.op, self.rhs)
+        if let InfixOp::Dot = self.op {
+            // `2.bit_count()` would lex as the float `2.`
+            let lhs_parens = self.op.lhs_needs_parens(&self.lhs)
+                || matches!(
+                    *self.lhs,
+                    Expression::Value(ValueExpression::I64(_) | ValueExpression::F64(_))
+                );
+            write_operand(f, &self.lhs, lhs_parens)?;
+            write!(f, ".")?;
+            return write_operand(f, &self.rhs, self.op.rhs_needs_parens(&self.rhs));
+        }
+        write_operand(f, &self.lhs, self.op.lhs_needs_parens(&self.lhs))?;
+        write!(f, " {} ", self.op)?;
+        write_operand(f, &self.rhs, self.op.rhs_needs_parens(&self.rhs))
+    }
+}
+
+impl Display for ComparisonExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (first_op, _) = &self.comparisons[0];
+        write_operand(f, &self.lhs, first_op.lhs_needs_parens(&self.lhs))?;
+        for (i, (op, rhs)) in self.comparisons.iter().enumerate() {
+            write!(f, " {op} ")?;
+            write_operand(f, rhs, self.rhs_needs_parens(i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SeriesExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let function = match self.op {
+            SeriesOp::Sum => "sum",
+            SeriesOp::Product => "math.prod",
+        };
+        write!(
+            f,
+            "{function}({} for {} in range({}, {} + 1))",
+            self.body, self.variable, self.lower, self.upper
+        )
     }
 }
 
@@ -155,6 +368,8 @@ impl Display for PrefixOp {
             PrefixOp::Not => write!(f, "not "),
             PrefixOp::Plus => write!(f, "+"),
             PrefixOp::Minus => write!(f, "-"),
+            PrefixOp::PlusMinus => write!(f, "±"),
+            PrefixOp::BitNot => write!(f, "~"),
         }
     }
 }
@@ -164,10 +379,16 @@ impl Display for InfixOp {
         match self {
             InfixOp::Add => write!(f, "+"),
             InfixOp::Subtract => write!(f, "-"),
+            InfixOp::PlusMinus => write!(f, "±"),
             InfixOp::Multiply => write!(f, "*"),
             InfixOp::Divide => write!(f, "/"),
             InfixOp::Modulo => write!(f, "%"),
             InfixOp::Exponent => write!(f, "**"),
+            InfixOp::BitAnd => write!(f, "&"),
+            InfixOp::BitOr => write!(f, "|"),
+            InfixOp::BitXor => write!(f, "^"),
+            InfixOp::ShiftLeft => write!(f, "<<"),
+            InfixOp::ShiftRight => write!(f, ">>"),
             InfixOp::And => write!(f, "and"),
             InfixOp::Or => write!(f, "or"),
             InfixOp::Equal => write!(f, "=="),
@@ -176,7 +397,212 @@ impl Display for InfixOp {
             InfixOp::LessThanOrEqual => write!(f, "<="),
             InfixOp::GreaterThan => write!(f, ">"),
             InfixOp::GreaterThanOrEqual => write!(f, ">="),
+            InfixOp::Is => write!(f, "is"),
+            InfixOp::IsNot => write!(f, "is not"),
+            InfixOp::In => write!(f, "in"),
+            InfixOp::NotIn => write!(f, "not in"),
             InfixOp::Dot => write!(f, "."),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fallible_iterator::FallibleIterator;
+    use quickcheck::{Arbitrary, Gen, QuickCheck, TestResult};
+
+    use super::*;
+    use crate::parser::{ParseOptions, Parser};
+
+    const INFIX_OPS: &[InfixOp] = &[
+        InfixOp::Add,
+        InfixOp::Subtract,
+        InfixOp::PlusMinus,
+        InfixOp::Multiply,
+        InfixOp::Divide,
+        InfixOp::Modulo,
+        InfixOp::Exponent,
+        InfixOp::BitAnd,
+        InfixOp::BitOr,
+        InfixOp::BitXor,
+        InfixOp::ShiftLeft,
+        InfixOp::ShiftRight,
+        InfixOp::And,
+        InfixOp::Or,
+        InfixOp::Equal,
+        InfixOp::NotEqual,
+        InfixOp::LessThan,
+        InfixOp::LessThanOrEqual,
+        InfixOp::GreaterThan,
+        InfixOp::GreaterThanOrEqual,
+        InfixOp::Is,
+        InfixOp::IsNot,
+        InfixOp::In,
+        InfixOp::NotIn,
+    ];
+
+    const COMPARISON_OPS: &[InfixOp] = &[
+        InfixOp::Equal,
+        InfixOp::NotEqual,
+        InfixOp::LessThan,
+        InfixOp::GreaterThanOrEqual,
+        InfixOp::Is,
+        InfixOp::IsNot,
+        InfixOp::In,
+        InfixOp::NotIn,
+    ];
+
+    const PREFIX_OPS: &[PrefixOp] = &[
+        PrefixOp::Not,
+        PrefixOp::Plus,
+        PrefixOp::Minus,
+        PrefixOp::PlusMinus,
+        PrefixOp::BitNot,
+    ];
+
+    const NAMES: &[&str] = &["x", "y", "total", "x_1", "π", "math", "sqrt", "bit_count"];
+
+    fn below(g: &mut Gen, n: usize) -> usize {
+        usize::arbitrary(g) % n
+    }
+
+    fn name(g: &mut Gen) -> String {
+        g.choose(NAMES).unwrap().to_string()
+    }
+
+    fn leaf(g: &mut Gen) -> Expression {
+        // the parser never produces negative literals, only negated ones
+        let value = match below(g, 5) {
+            0 => ValueExpression::Bool(bool::arbitrary(g)),
+            1 => ValueExpression::I64(i64::arbitrary(g).saturating_abs()),
+            2 => ValueExpression::F64(f64::from(u32::arbitrary(g)) / 64.0),
+            3 => {
+                let chars = ['a', 'Z', ' ', '\'', '"', '\\', '\n', '\t', 'π'];
+                let len = below(g, 4);
+                ValueExpression::Str((0..len).map(|_| *g.choose(&chars).unwrap()).collect())
+            }
+            _ => ValueExpression::Variable(name(g)),
+        };
+        Expression::Value(value)
+    }
+
+    fn expression(g: &mut Gen, depth: usize) -> Expression {
+        if depth == 0 {
+            return leaf(g);
+        }
+        let sub = |g: &mut Gen| Box::new(expression(g, depth - 1));
+        match below(g, 8) {
+            0 => leaf(g),
+            1 | 2 => Expression::Infix(InfixExpression {
+                op: g.choose(INFIX_OPS).unwrap().clone(),
+                lhs: sub(g),
+                rhs: sub(g),
+            }),
+            3 => Expression::Prefix(PrefixExpression {
+                op: g.choose(PREFIX_OPS).unwrap().clone(),
+                rhs: sub(g),
+            }),
+            4 => Expression::Comparison(ComparisonExpression {
+                lhs: sub(g),
+                comparisons: (0..2 + below(g, 2))
+                    .map(|_| (g.choose(COMPARISON_OPS).unwrap().clone(), *sub(g)))
+                    .collect(),
+            }),
+            5 => Expression::Fn(FnExpression {
+                name: name(g),
+                parameters: (0..below(g, 3)).map(|_| *sub(g)).collect(),
+            }),
+            6 => Expression::Tuple((0..below(g, 3)).map(|_| *sub(g)).collect()),
+            _ => {
+                // a method call or module attribute, like `n.bit_count()` or `math.pi`
+                let rhs = match bool::arbitrary(g) {
+                    true => Expression::Fn(FnExpression {
+                        name: name(g),
+                        parameters: (0..below(g, 2)).map(|_| *sub(g)).collect(),
+                    }),
+                    false => Expression::Value(ValueExpression::Variable(name(g))),
+                };
+                Expression::Infix(InfixExpression {
+                    op: InfixOp::Dot,
+                    lhs: sub(g),
+                    rhs: Box::new(rhs),
+                })
+            }
+        }
+    }
+
+    // series print as python generator expressions, which the parser doesn't read back
+    impl Arbitrary for Expression {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let depth = below(g, 6);
+            expression(g, depth)
+        }
+
+        /// Shrinks to each subexpression, to find the smallest failing case
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let children = match self {
+                Expression::Value(_) => vec![],
+                Expression::Fn(expr) => expr.parameters.clone(),
+                Expression::Infix(expr) => vec![*expr.lhs.clone(), *expr.rhs.clone()],
+                Expression::Prefix(expr) => vec![*expr.rhs.clone()],
+                Expression::Comparison(expr) => std::iter::once(*expr.lhs.clone())
+                    .chain(expr.comparisons.iter().map(|(_, rhs)| rhs.clone()))
+                    .collect(),
+                Expression::Tuple(exprs) => exprs.clone(),
+                Expression::Series(expr) => vec![*expr.body.clone()],
+            };
+            Box::new(children.into_iter())
+        }
+    }
+
+    fn round_trip(expr: Expression) -> TestResult {
+        let printed = expr.to_string();
+        // python's `^` is xor, which the default options read as `**`
+        let options = ParseOptions {
+            caret_is_xor: true,
+            ..Default::default()
+        };
+        let parsed: Result<Vec<_>, _> = Parser::new(&printed, options).collect();
+        match parsed {
+            Ok(parsed) if parsed == [expr.clone()] => TestResult::passed(),
+            Ok(parsed) => {
+                TestResult::error(format!("{printed} parsed as {parsed:?}, not {expr:?}"))
+            }
+            Err(error) => TestResult::error(format!("{printed} failed to parse: {error}")),
+        }
+    }
+
+    #[test]
+    fn printed_expressions_parse_back_identically() {
+        QuickCheck::new()
+            .tests(2000)
+            .quickcheck(round_trip as fn(Expression) -> TestResult);
+    }
+
+    #[test]
+    fn prints_minimal_parens() {
+        let cases = [
+            ("(1 + 2) * 3", "(1 + 2) * 3"),
+            ("1 + (2 * 3)", "1 + 2 * 3"),
+            ("(1 - 2) - 3", "1 - 2 - 3"),
+            ("1 - (2 - 3)", "1 - (2 - 3)"),
+            ("2 ** 3 ** 2", "2 ** 3 ** 2"),
+            ("(2 ** 3) ** 2", "(2 ** 3) ** 2"),
+            ("-2 ** 2", "-2 ** 2"),
+            ("(-2) ** 2", "(-2) ** 2"),
+            ("2 ** -1", "2 ** -1"),
+            ("not (a and b)", "not (a and b)"),
+            ("(not a) == b", "(not a) == b"),
+            ("(1 < 2) < 3", "(1 < 2) < 3"),
+            ("1 < 2 < 3", "1 < 2 < 3"),
+            ("(1, 2.0, 'a')", "(1, 2.0, 'a')"),
+            ("math.sqrt(16)", "math.sqrt(16)"),
+            ("(255).bit_count()", "(255).bit_count()"),
+        ];
+        for (input, expected) in cases {
+            let mut parser = Parser::new(input, ParseOptions::default());
+            let expr = parser.next().unwrap().unwrap();
+            assert_eq!(expr.to_string(), expected, "printing {input}");
+        }
+    }
+}
@@ -0,0 +1,660 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{
+    MLResult,
+    error::MathlineError,
+    parser::{
+        MAX_NESTING, check_depth,
+        expression::{
+            ComparisonExpression, Expression, FnExpression, InfixExpression, InfixOp,
+            PrefixExpression, PrefixOp, SeriesExpression, SeriesOp, ValueExpression,
+        },
+    },
+};
+
+/// Returns true if the input uses LaTeX commands like `\frac`, braced groups
+/// like `2^{10}` or `$` delimiters, none of which appear in python expressions
+pub fn is_latex(input: &str) -> bool {
+    if input.contains(['{', '$']) {
+        return true;
+    }
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek().is_some_and(char::is_ascii_alphabetic) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Parses a LaTeX math expression like `\frac{3}{4} \cdot \sqrt{16}`
+/// into the same expression tree as the equivalent python
+pub fn parse(input: &str) -> MLResult<Expression> {
+    let tokens = LatexLexer::new(strip_delimiters(input)).lex()?;
+    let mut parser = LatexParser {
+        tokens,
+        position: 0,
+        abs_depth: 0,
+        nesting: 0,
+    };
+    let expression = parser.parse_expr()?;
+    match parser.peek() {
+        None => check_depth(expression),
+        Some(_) => Err(MathlineError::InvalidSyntax),
+    }
+}
+
+/// Strips math mode delimiters like `$...$`, `$$...$$`, `\(...\)` and `\[...\]`
+fn strip_delimiters(input: &str) -> &str {
+    let input = input.trim();
+    for (open, close) in [("$$", "$$"), ("$", "$"), ("\\(", "\\)"), ("\\[", "\\]")] {
+        if let Some(inner) = input
+            .strip_prefix(open)
+            .and_then(|input| input.strip_suffix(close))
+        {
+            return inner.trim();
+        }
+    }
+    input
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LatexToken {
+    Number(String),
+    Letter(char),
+    Command(String),
+    Char(char),
+}
+
+struct LatexLexer<'a> {
+    input: Peekable<Chars<'a>>,
+}
+
+impl<'a> LatexLexer<'a> {
+    fn new(input: &'a str) -> Self {
+        LatexLexer {
+            input: input.chars().peekable(),
+        }
+    }
+
+    fn lex(mut self) -> MLResult<Vec<LatexToken>> {
+        let mut tokens = vec![];
+        while let Some(c) = self.input.next() {
+            let token = match c {
+                _ if c.is_whitespace() || c == '~' => continue,
+                '\\' => match self.lex_command()? {
+                    // spacing and sizing commands don't change the math
+                    Some(command) => command,
+                    None => continue,
+                },
+                _ if c.is_ascii_digit() || c == '.' => self.lex_number(c),
+                _ if c.is_alphabetic() => LatexToken::Letter(c),
+                _ => LatexToken::Char(c),
+            };
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    fn lex_number(&mut self, c: char) -> LatexToken {
+        let mut number = c.to_string();
+        while let Some(c) = self.input.peek() {
+            if c.is_ascii_digit() || *c == '.' {
+                number.push(*c);
+                self.input.next();
+            } else {
+                break;
+            }
+        }
+        LatexToken::Number(number)
+    }
+
+    /// Lexes a command after its backslash. Returns `None` for commands that
+    /// only affect typesetting, like `\,` or `\left`.
+    fn lex_command(&mut self) -> MLResult<Option<LatexToken>> {
+        let Some(c) = self.input.next() else {
+            return Err(MathlineError::InvalidEOF);
+        };
+        if !c.is_ascii_alphabetic() {
+            return Ok(match c {
+                ',' | ';' | ':' | '!' | ' ' => None,
+                '{' => Some(LatexToken::Char('(')),
+                '}' => Some(LatexToken::Char(')')),
+                '%' => Some(LatexToken::Char('%')),
+                _ => return Err(MathlineError::InvalidChar(c)),
+            });
+        }
+        let mut name = c.to_string();
+        while let Some(c) = self.input.peek() {
+            if c.is_ascii_alphabetic() {
+                name.push(*c);
+                self.input.next();
+            } else {
+                break;
+            }
+        }
+        Ok(match name.as_str() {
+            "quad" | "qquad" | "displaystyle" | "textstyle" | "big" | "Big" | "bigg" | "Bigg" => {
+                None
+            }
+            "left" | "right" | "bigl" | "bigr" | "Bigl" | "Bigr" => {
+                // `\left.` is an invisible delimiter
+                if self.input.peek() == Some(&'.') {
+                    self.input.next();
+                }
+                None
+            }
+            "lvert" | "rvert" | "vert" => Some(LatexToken::Char('|')),
+            "lbrace" => Some(LatexToken::Char('(')),
+            "rbrace" => Some(LatexToken::Char(')')),
+            _ => Some(LatexToken::Command(name)),
+        })
+    }
+}
+
+struct LatexParser {
+    tokens: Vec<LatexToken>,
+    position: usize,
+    /// How many absolute value bars `|` are open
+    abs_depth: usize,
+    /// How many calls to [LatexParser::parse_unary] and [LatexParser::parse_atom]
+    /// are in progress, which every recursion in the parser passes through
+    nesting: usize,
+}
+
+impl LatexParser {
+    /// Runs a recursive step of the parser, failing instead of nesting deeper than [MAX_NESTING]
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> MLResult<Expression>,
+    ) -> MLResult<Expression> {
+        if self.nesting >= MAX_NESTING {
+            return Err(MathlineError::NestedTooDeeply(MAX_NESTING));
+        }
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        check_depth(result?)
+    }
+
+    fn peek(&self) -> Option<&LatexToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<LatexToken> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_is_char(&self, c: char) -> bool {
+        self.peek() == Some(&LatexToken::Char(c))
+    }
+
+    fn expect_char(&mut self, c: char) -> MLResult<()> {
+        match self.next() {
+            Some(LatexToken::Char(next)) if next == c => Ok(()),
+            Some(_) => Err(MathlineError::InvalidSyntax),
+            None => Err(MathlineError::InvalidEOF),
+        }
+    }
+
+    /// Parses a chain of comparisons, the loosest binding level of LaTeX math
+    fn parse_expr(&mut self) -> MLResult<Expression> {
+        let lhs = self.parse_additive()?;
+        let mut comparisons = vec![];
+        while let Some(op) = self.peek_comparison() {
+            self.next();
+            comparisons.push((op, self.parse_additive()?));
+        }
+        if comparisons.len() <= 1 {
+            return check_depth(match comparisons.pop() {
+                Some((op, rhs)) => infix(op, lhs, rhs),
+                None => lhs,
+            });
+        }
+        check_depth(Expression::Comparison(ComparisonExpression {
+            lhs: Box::new(lhs),
+            comparisons,
+        }))
+    }
+
+    fn peek_comparison(&self) -> Option<InfixOp> {
+        Some(match self.peek()? {
+            LatexToken::Char('=') => InfixOp::Equal,
+            LatexToken::Char('<') => InfixOp::LessThan,
+            LatexToken::Char('>') => InfixOp::GreaterThan,
+            LatexToken::Command(command) => match command.as_str() {
+                "neq" | "ne" => InfixOp::NotEqual,
+                "leq" | "le" | "leqslant" => InfixOp::LessThanOrEqual,
+                "geq" | "ge" | "geqslant" => InfixOp::GreaterThanOrEqual,
+                "in" => InfixOp::In,
+                "notin" => InfixOp::NotIn,
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+
+    fn parse_additive(&mut self) -> MLResult<Expression> {
+        let mut lhs = self.parse_term()?;
+        loop {
+            let op = match self.peek() {
+                Some(LatexToken::Char('+')) => InfixOp::Add,
+                Some(LatexToken::Char('-' | '−')) => InfixOp::Subtract,
+                Some(LatexToken::Command(command)) if command == "pm" => InfixOp::PlusMinus,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.parse_term()?;
+            lhs = check_depth(infix(op, lhs, rhs))?;
+        }
+    }
+
+    fn parse_term(&mut self) -> MLResult<Expression> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(LatexToken::Char('*')) => Some(InfixOp::Multiply),
+                Some(LatexToken::Char('/')) => Some(InfixOp::Divide),
+                Some(LatexToken::Command(command)) => match command.as_str() {
+                    "cdot" | "times" | "ast" => Some(InfixOp::Multiply),
+                    "div" => Some(InfixOp::Divide),
+                    "bmod" | "mod" => Some(InfixOp::Modulo),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(op) = op {
+                self.next();
+                lhs = check_depth(infix(op, lhs, self.parse_unary()?))?;
+            } else if self.starts_operand() {
+                // implicit multiplication, as in `2x` or `3(4 + 5)`
+                lhs = check_depth(infix(InfixOp::Multiply, lhs, self.parse_unary()?))?;
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    /// Returns true if the next token begins an operand rather than an operator
+    fn starts_operand(&self) -> bool {
+        match self.peek() {
+            Some(LatexToken::Number(_) | LatexToken::Letter(_)) => true,
+            Some(LatexToken::Char('(' | '{' | '[')) => true,
+            Some(LatexToken::Char('|')) => self.abs_depth == 0,
+            Some(LatexToken::Command(command)) => {
+                is_operand_command(command) || function_name(command).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_unary(&mut self) -> MLResult<Expression> {
+        self.nested(Self::parse_prefix)
+    }
+
+    fn parse_prefix(&mut self) -> MLResult<Expression> {
+        let op = match self.peek() {
+            Some(LatexToken::Char('-' | '−')) => PrefixOp::Minus,
+            Some(LatexToken::Char('+')) => PrefixOp::Plus,
+            Some(LatexToken::Command(command)) if command == "pm" => PrefixOp::PlusMinus,
+            _ => return self.parse_power(),
+        };
+        self.next();
+        let rhs = Box::new(self.parse_unary()?);
+        Ok(Expression::Prefix(PrefixExpression { op, rhs }))
+    }
+
+    fn parse_power(&mut self) -> MLResult<Expression> {
+        let base = self.parse_postfix()?;
+        if !self.next_is_char('^') {
+            return Ok(base);
+        }
+        self.next();
+        let exponent = self.parse_script()?;
+        Ok(infix(InfixOp::Exponent, base, exponent))
+    }
+
+    fn parse_postfix(&mut self) -> MLResult<Expression> {
+        let mut expr = self.parse_atom()?;
+        loop {
+            expr = check_depth(match self.peek() {
+                Some(LatexToken::Char('!')) => call("factorial", vec![expr]),
+                Some(LatexToken::Char('%')) => infix(
+                    InfixOp::Divide,
+                    expr,
+                    Expression::Value(ValueExpression::I64(100)),
+                ),
+                _ => return Ok(expr),
+            })?;
+            self.next();
+        }
+    }
+
+    /// Parses a superscript, subscript or command argument, which is a braced group or
+    /// a single token. As in LaTeX, an unbraced number is only its first digit,
+    /// so `\frac12` is `1 / 2` and `x^23` is `x^2 * 3`.
+    fn parse_script(&mut self) -> MLResult<Expression> {
+        match self.peek() {
+            Some(LatexToken::Char('{')) => self.parse_group(),
+            Some(LatexToken::Char('-' | '+' | '−')) => self.parse_unary(),
+            Some(LatexToken::Number(number))
+                if number.len() > 1 && number.starts_with(|c: char| c.is_ascii_digit()) =>
+            {
+                let (digit, rest) = number.split_at(1);
+                let digit = parse_number(digit)?;
+                self.tokens[self.position] = LatexToken::Number(rest.to_string());
+                Ok(digit)
+            }
+            _ => self.parse_atom(),
+        }
+    }
+
+    /// Parses a braced group like `{x + 1}`
+    fn parse_group(&mut self) -> MLResult<Expression> {
+        self.expect_char('{')?;
+        let expr = self.parse_expr()?;
+        self.expect_char('}')?;
+        Ok(expr)
+    }
+
+    fn parse_atom(&mut self) -> MLResult<Expression> {
+        self.nested(Self::parse_operand)
+    }
+
+    fn parse_operand(&mut self) -> MLResult<Expression> {
+        let Some(token) = self.next() else {
+            return Err(MathlineError::InvalidEOF);
+        };
+        match token {
+            LatexToken::Number(number) => parse_number(&number),
+            LatexToken::Letter(letter) => {
+                let mut name = letter.to_string();
+                // a subscripted variable like `x_1`
+                if self.next_is_char('_') {
+                    self.next();
+                    name.push('_');
+                    name.push_str(&self.parse_script()?.to_string());
+                }
+                Ok(Expression::Value(ValueExpression::Variable(name)))
+            }
+            LatexToken::Char(open @ ('(' | '[' | '{')) => {
+                let close = match open {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                };
+                let expr = self.parse_expr()?;
+                self.expect_char(close)?;
+                Ok(expr)
+            }
+            LatexToken::Char('|') => {
+                self.abs_depth += 1;
+                let expr = self.parse_expr();
+                self.abs_depth -= 1;
+                let expr = expr?;
+                self.expect_char('|')?;
+                Ok(call("abs", vec![expr]))
+            }
+            LatexToken::Command(command) => self.parse_command(&command),
+            LatexToken::Char(c) => Err(MathlineError::InvalidChar(c)),
+        }
+    }
+
+    fn parse_command(&mut self, command: &str) -> MLResult<Expression> {
+        let variable = |name: &str| -> MLResult<_> {
+            Ok(Expression::Value(ValueExpression::Variable(name.into())))
+        };
+        match command {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.parse_script()?;
+                let denominator = self.parse_script()?;
+                Ok(infix(InfixOp::Divide, numerator, denominator))
+            }
+            "sqrt" => self.parse_sqrt(),
+            "sum" => self.parse_series(SeriesOp::Sum),
+            "prod" => self.parse_series(SeriesOp::Product),
+            "pi" => variable("pi"),
+            "tau" => variable("tau"),
+            "phi" | "varphi" => variable("φ"),
+            "infty" => variable("inf"),
+            "mathrm" | "text" | "operatorname" => self.parse_text(),
+            _ => match function_name(command) {
+                Some(name) => self.parse_function(name),
+                None => Err(MathlineError::FunctionIsNotSupported(format!(
+                    "\\{command}"
+                ))),
+            },
+        }
+    }
+
+    /// Parses `\sqrt{x}` as `sqrt(x)`, `\sqrt[3]{x}` as `cbrt(x)`
+    /// and `\sqrt[n]{x}` as `x ** (1 / n)`
+    fn parse_sqrt(&mut self) -> MLResult<Expression> {
+        let mut index = None;
+        if self.next_is_char('[') {
+            self.next();
+            index = Some(self.parse_expr()?);
+            self.expect_char(']')?;
+        }
+        let radicand = self.parse_script()?;
+        Ok(match index {
+            None | Some(Expression::Value(ValueExpression::I64(2))) => call("sqrt", vec![radicand]),
+            Some(Expression::Value(ValueExpression::I64(3))) => call("cbrt", vec![radicand]),
+            Some(index) => {
+                let one = Expression::Value(ValueExpression::I64(1));
+                infix(
+                    InfixOp::Exponent,
+                    radicand,
+                    infix(InfixOp::Divide, one, index),
+                )
+            }
+        })
+    }
+
+    /// Parses `\sum_{i=1}^{n} body` and `\prod_{i=1}^{n} body`
+    fn parse_series(&mut self, op: SeriesOp) -> MLResult<Expression> {
+        self.expect_char('_')?;
+        let braced = self.next_is_char('{');
+        if braced {
+            self.next();
+        }
+        let variable = match self.next() {
+            Some(LatexToken::Letter(letter)) => letter.to_string(),
+            Some(_) => return Err(MathlineError::InvalidSyntax),
+            None => return Err(MathlineError::InvalidEOF),
+        };
+        self.expect_char('=')?;
+        let lower = if braced {
+            let lower = self.parse_additive()?;
+            self.expect_char('}')?;
+            lower
+        } else {
+            self.parse_atom()?
+        };
+        self.expect_char('^')?;
+        let upper = self.parse_script()?;
+        // like `\sum_{i=1}^{n} i^2 + 1`, the body extends over one term
+        let body = self.parse_term()?;
+        Ok(Expression::Series(SeriesExpression {
+            op,
+            variable,
+            lower: Box::new(lower),
+            upper: Box::new(upper),
+            body: Box::new(body),
+        }))
+    }
+
+    /// Parses a function application like `\sin(x)`, `\sin 2x`,
+    /// `\sin^2 x`, `\sin^{-1} x` or `\log_{2} 8`
+    fn parse_function(&mut self, name: &str) -> MLResult<Expression> {
+        let mut name = name.to_string();
+        let mut power = None;
+        let mut base = None;
+        if self.next_is_char('^') {
+            self.next();
+            match self.parse_script()? {
+                Expression::Prefix(PrefixExpression {
+                    op: PrefixOp::Minus,
+                    rhs,
+                }) if matches!(*rhs, Expression::Value(ValueExpression::I64(1))) => {
+                    name = format!("a{name}")
+                }
+                exponent => power = Some(exponent),
+            }
+        }
+        if name == "log" && self.next_is_char('_') {
+            self.next();
+            base = Some(self.parse_script()?);
+        }
+
+        let argument = if self.next_is_char('(') || self.next_is_char('{') {
+            self.parse_atom()?
+        } else {
+            // an argument without parentheses extends over implicit multiplication,
+            // so `\sin 2x` is `sin(2 * x)`, but stops at the next function
+            let mut argument = self.parse_power()?;
+            while self.starts_operand() && !self.next_is_function() {
+                argument = check_depth(infix(InfixOp::Multiply, argument, self.parse_power()?))?;
+            }
+            argument
+        };
+
+        let mut parameters = vec![argument];
+        parameters.extend(base);
+        let expr = call(&name, parameters);
+        Ok(match power {
+            Some(power) => infix(InfixOp::Exponent, expr, power),
+            None => expr,
+        })
+    }
+
+    fn next_is_function(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(LatexToken::Command(command))
+                if function_name(command).is_some() || command == "sum" || command == "prod"
+        )
+    }
+
+    /// Parses the braced text of `\text{...}` or `\operatorname{...}`
+    /// as a variable or a function name
+    fn parse_text(&mut self) -> MLResult<Expression> {
+        self.expect_char('{')?;
+        let mut name = String::new();
+        while let Some(LatexToken::Letter(letter)) = self.peek() {
+            name.push(*letter);
+            self.next();
+        }
+        self.expect_char('}')?;
+        if self.next_is_char('(') {
+            return self.parse_function(&name);
+        }
+        Ok(Expression::Value(ValueExpression::Variable(name)))
+    }
+}
+
+/// Returns the evaluator's name for a LaTeX function command like `\sin`
+fn function_name(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "sin" => "sin",
+        "cos" => "cos",
+        "tan" => "tan",
+        "arcsin" => "asin",
+        "arccos" => "acos",
+        "arctan" => "atan",
+        "ln" => "ln",
+        "log" => "log",
+        "exp" => "exp",
+        _ => return None,
+    })
+}
+
+/// Returns true for commands that begin an operand, like `\frac` or `\pi`
+fn is_operand_command(command: &str) -> bool {
+    matches!(
+        command,
+        "frac"
+            | "dfrac"
+            | "tfrac"
+            | "sqrt"
+            | "sum"
+            | "prod"
+            | "pi"
+            | "tau"
+            | "phi"
+            | "varphi"
+            | "infty"
+            | "mathrm"
+            | "text"
+            | "operatorname"
+    )
+}
+
+fn parse_number(number: &str) -> MLResult<Expression> {
+    let invalid = || MathlineError::InvalidNumber(number.to_string());
+    if !number.contains('.')
+        && let Ok(n) = number.parse()
+    {
+        return Ok(Expression::Value(ValueExpression::I64(n)));
+    }
+    let n = number.parse().map_err(|_| invalid())?;
+    Ok(Expression::Value(ValueExpression::F64(n)))
+}
+
+fn infix(op: InfixOp, lhs: Expression, rhs: Expression) -> Expression {
+    Expression::Infix(InfixExpression {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    })
+}
+
+fn call(name: &str, parameters: Vec<Expression>) -> Expression {
+    Expression::Fn(FnExpression {
+        name: name.to_string(),
+        parameters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parses_latex() {
+        let cases = [
+            (r"\frac{3}{4}", "3 / 4"),
+            (r"\frac12", "1 / 2"),
+            (r"\dfrac{x+1}2", "(x + 1) / 2"),
+            (r"x^23", "x ** 2 * 3"),
+            (r"x^{23}", "x ** 23"),
+            (r"x^2.5", "x ** 2 * 0.5"),
+            (r"x_12", "x_1 * 2"),
+            (r"\sqrt{16}", "sqrt(16)"),
+            (r"\sqrt[3]{27}", "cbrt(27)"),
+            (r"\sqrt[4]{16}", "16 ** (1 / 4)"),
+            (r"\left| -3 \right|", "abs(-3)"),
+            (r"|a - b|", "abs(a - b)"),
+            (r"3 \cdot 4", "3 * 4"),
+            (r"6 \div 3", "6 / 3"),
+            (
+                r"\sum_{i=1}^{10} i^2",
+                "sum(i ** 2 for i in range(1, 10 + 1))",
+            ),
+            (
+                r"\prod_{k=1}^{5} k",
+                "math.prod(k for k in range(1, 5 + 1))",
+            ),
+            (r"2\pi r", "2 * pi * r"),
+            (r"3(4 + 5)", "3 * (4 + 5)"),
+            (r"2\sqrt{2}", "2 * sqrt(2)"),
+            (r"\sin x", "sin(x)"),
+            (r"x \leq 2", "x <= 2"),
+            (r"5!", "factorial(5)"),
+        ];
+        for (input, expected) in cases {
+            let expr = super::parse(input).unwrap_or_else(|e| panic!("parsing {input}: {e}"));
+            assert_eq!(expr.to_string(), expected, "parsing {input}");
+        }
+    }
+}
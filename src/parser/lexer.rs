@@ -1,16 +1,21 @@
-use std::{iter::Peekable, str::Chars};
+use std::{collections::VecDeque, iter::Peekable, str::Chars};
 
 use fallible_iterator::FallibleIterator;
 
 use crate::{
     MLResult,
     error::MathlineError,
-    parser::token::{Op, Token},
+    parser::{
+        Notation, ParseOptions,
+        token::{Op, Token},
+    },
 };
 
 pub struct Lexer<'a> {
     input: Peekable<Chars<'a>>,
-    next: Option<Token>,
+    /// Tokens already lexed from a single char like `½` that expands to several tokens
+    pending: VecDeque<Token>,
+    options: ParseOptions,
 }
 
 impl<'a> FallibleIterator for Lexer<'a> {
@@ -21,7 +26,7 @@ impl<'a> FallibleIterator for Lexer<'a> {
     /// Does not implement std Iterator trait because
     /// its outer type is a Result.
     fn next(&mut self) -> MLResult<Option<Token>> {
-        if let Some(token) = self.next.take() {
+        if let Some(token) = self.pending.pop_front() {
             return Ok(Some(token));
         }
         while let Some(c) = self.input.next() {
@@ -45,19 +50,29 @@ impl<'a> FallibleIterator for Lexer<'a> {
                     Token::Op(Op::Divide)
                 }
                 '%' => Token::Op(Op::Modulo),
+                '^' if self.options.caret_is_xor => Token::Op(Op::BitXor),
                 '^' => Token::Op(Op::Exponent),
+                '~' => Token::Op(Op::BitNot),
+                '.' if self.input.peek().is_some_and(char::is_ascii_digit) => self.lex_number(c)?,
                 '.' => Token::Op(Op::Dot),
                 '&' => {
                     if let Some('&') = self.input.peek() {
                         self.input.next();
-                    };
-                    Token::Op(Op::And)
+                        Token::Op(Op::And)
+                    } else {
+                        Token::Op(Op::BitAnd)
+                    }
                 }
                 '|' => {
-                    if let Some('|') = self.input.peek() {
+                    // in math notation `||x| - 1|` nests absolute value bars
+                    if self.options.notation != Notation::Math
+                        && let Some('|') = self.input.peek()
+                    {
                         self.input.next();
-                    };
-                    Token::Op(Op::Or)
+                        Token::Op(Op::Or)
+                    } else {
+                        Token::Op(Op::BitOr)
+                    }
                 }
                 '=' => {
                     if let Some('=') = self.input.peek() {
@@ -73,50 +88,50 @@ impl<'a> FallibleIterator for Lexer<'a> {
                         Token::Op(Op::Not)
                     }
                 }
-                '<' => {
-                    if let Some('=') = self.input.peek() {
+                '<' => match self.input.peek() {
+                    Some('=') => {
                         self.input.next();
                         Token::Op(Op::LessThanOrEqual)
-                    } else {
-                        Token::Op(Op::LessThan)
                     }
-                }
-                '>' => {
-                    if let Some('=') = self.input.peek() {
+                    Some('<') => {
+                        self.input.next();
+                        Token::Op(Op::ShiftLeft)
+                    }
+                    _ => Token::Op(Op::LessThan),
+                },
+                '>' => match self.input.peek() {
+                    Some('=') => {
                         self.input.next();
                         Token::Op(Op::GreaterThanOrEqual)
-                    } else {
-                        Token::Op(Op::GreaterThan)
                     }
-                }
-                '≠' => Token::Op(Op::NotEqual),        // alt for neq
-                '≥' => Token::Op(Op::LessThanOrEqual), // alt for lte
-                '≤' => Token::Op(Op::GreaterThanOrEqual), // alt for gte
+                    Some('>') => {
+                        self.input.next();
+                        Token::Op(Op::ShiftRight)
+                    }
+                    _ => Token::Op(Op::GreaterThan),
+                },
+                '"' | '\'' => self.lex_string(c)?,
+                '≠' => Token::Op(Op::NotEqual),
+                '≤' | '⩽' => Token::Op(Op::LessThanOrEqual),
+                '≥' | '⩾' => Token::Op(Op::GreaterThanOrEqual),
+                '×' | '·' | '⋅' | '∗' => Token::Op(Op::Multiply),
+                '÷' | '∕' => Token::Op(Op::Divide),
+                '−' => Token::Op(Op::Minus),
+                '±' => Token::Op(Op::PlusMinus),
+                '∧' => Token::Op(Op::And),
+                '∨' => Token::Op(Op::Or),
+                '¬' => Token::Op(Op::Not),
+                '√' => Token::Op(Op::Sqrt),
+                '∛' => Token::Op(Op::Cbrt),
+                '∞' => Token::Symbol(c.to_string()),
                 '(' => Token::LeftParen,
                 ')' => Token::RightParen,
                 ',' => Token::Comma,
-                '⁰' => {
-                    self.next = Some(Token::I64(0));
-                    Token::Op(Op::Exponent)
-                }
-                '¹' => {
-                    self.next = Some(Token::I64(1));
-                    Token::Op(Op::Exponent)
-                }
-                '²' => {
-                    self.next = Some(Token::I64(2));
-                    Token::Op(Op::Exponent)
-                }
-                '³' => {
-                    self.next = Some(Token::I64(3));
-                    Token::Op(Op::Exponent)
-                }
-                '⁴' => {
-                    self.next = Some(Token::I64(4));
-                    Token::Op(Op::Exponent)
-                }
+                '⁺' | '⁻' => self.lex_superscript(c)?,
+                _ if superscript_digit(c).is_some() => self.lex_superscript(c)?,
+                _ if vulgar_fraction(c).is_some() => self.lex_fraction(c),
                 _ if c.is_ascii_digit() => self.lex_number(c)?,
-                _ if c.is_ascii_alphabetic() => self.lex_symbol(c)?,
+                _ if c.is_alphabetic() || c == '_' => self.lex_symbol(c)?,
                 _ => return Err(MathlineError::InvalidChar(c)),
             };
             return Ok(Some(token));
@@ -127,45 +142,205 @@ impl<'a> FallibleIterator for Lexer<'a> {
 
 impl<'a> Lexer<'a> {
     /// Return a new lexer from the input
-    pub fn new(input: &'a str) -> Self {
+    pub fn new(input: &'a str, options: ParseOptions) -> Self {
         let input = input.chars().peekable();
-        Lexer { input, next: None }
+        Lexer {
+            input,
+            pending: VecDeque::new(),
+            options,
+        }
     }
 
+    /// Lexes any python numeric literal: `123`, `1_000`, `1.5`, `.5`, `1.`,
+    /// `1e6`, `2.5E-3`, `0xFF`, `0o17` and `0b1010`
     fn lex_number(&mut self, c: char) -> MLResult<Token> {
-        let mut whole = c.to_digit(10).unwrap() as i64;
-        let mut fraction = 0.0f64;
-        let mut fraction_multiplier = 0.0f64;
+        if c == '0' {
+            let radix = match self.input.peek() {
+                Some('x' | 'X') => Some(16),
+                Some('o' | 'O') => Some(8),
+                Some('b' | 'B') => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                let prefix = self.input.next().unwrap();
+                return self.lex_radix_int(prefix, radix);
+            }
+        }
 
-        while let Some(c) = self.input.peek() {
-            if let Some(digit) = c.to_digit(10) {
-                if fraction_multiplier == 0.0 {
-                    whole *= 10;
-                    whole += digit as i64;
-                } else {
-                    fraction += fraction_multiplier * (digit as f64);
-                    fraction_multiplier *= 0.1;
-                }
-                self.input.next();
-            } else if *c == '.' {
-                fraction_multiplier = 0.1;
+        let mut literal = String::from(c);
+        let mut is_float = c == '.';
+        self.lex_digits(&mut literal, 10)?;
+
+        if !is_float && self.input.peek() == Some(&'.') {
+            self.input.next();
+            literal.push('.');
+            is_float = true;
+            self.lex_digits(&mut literal, 10)?;
+        }
+
+        if self.exponent_follows() {
+            literal.push(self.input.next().unwrap());
+            if let Some(sign @ ('+' | '-')) = self.input.peek() {
+                literal.push(*sign);
                 self.input.next();
-            } else {
-                break;
             }
+            self.lex_digits(&mut literal, 10)?;
+            is_float = true;
+        }
+
+        if !is_float && let Ok(n) = literal.parse::<i64>() {
+            return Ok(self.lex_mixed_number(n));
+        }
+
+        // rust's float parsing is correctly rounded. Integers
+        // too large for an i64 are approximated as floats
+        literal
+            .parse::<f64>()
+            .map(Token::F64)
+            .map_err(|_| MathlineError::InvalidNumber(literal))
+    }
+
+    /// Lexes the digits of a hex, octal or binary literal after its prefix
+    fn lex_radix_int(&mut self, prefix: char, radix: u32) -> MLResult<Token> {
+        // python allows an underscore directly after the prefix, e.g. `0x_FF`
+        if self.input.peek() == Some(&'_') {
+            self.input.next();
+        }
+        let mut literal = String::new();
+        let invalid = |literal: &str| MathlineError::InvalidNumber(format!("0{prefix}{literal}"));
+        self.lex_digits(&mut literal, radix)
+            .map_err(|_| invalid(&literal))?;
+        // a digit out of the radix, like the 2 in `0b12`, is invalid rather than a new number
+        if self.input.peek().is_some_and(|c| c.is_alphanumeric()) {
+            while let Some(c) = self.input.next_if(|c| c.is_alphanumeric()) {
+                literal.push(c);
+            }
+            return Err(invalid(&literal));
         }
+        i64::from_str_radix(&literal, radix)
+            .map(Token::I64)
+            .map_err(|_| invalid(&literal))
+    }
 
-        if fraction == 0.0 {
-            Ok(Token::I64(whole))
-        } else {
-            Ok(Token::F64(whole as f64 + fraction))
+    /// Pushes digits onto the literal, dropping underscores that separate digits.
+    /// As in python, an underscore anywhere else makes the literal invalid, like `1__0`
+    fn lex_digits(&mut self, literal: &mut String, radix: u32) -> MLResult<()> {
+        let mut after_digit = literal.chars().last().is_some_and(|c| c.is_digit(radix));
+        loop {
+            match self.lookahead(0) {
+                Some(c) if c.is_digit(radix) => {
+                    literal.push(c);
+                    self.input.next();
+                    after_digit = true;
+                }
+                Some('_')
+                    if after_digit && self.lookahead(1).is_some_and(|c| c.is_digit(radix)) =>
+                {
+                    self.input.next();
+                    after_digit = false;
+                }
+                Some('_') => {
+                    while let Some(c) = self.input.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                        literal.push(c);
+                    }
+                    return Err(MathlineError::InvalidNumber(literal.clone()));
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Returns true if the input continues with an exponent like `e6` or `E-3`
+    fn exponent_follows(&self) -> bool {
+        if !matches!(self.lookahead(0), Some('e' | 'E')) {
+            return false;
+        }
+        match self.lookahead(1) {
+            Some('+' | '-') => self.lookahead(2).is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    /// Returns the nth char of the remaining input without consuming anything
+    fn lookahead(&self, n: usize) -> Option<char> {
+        self.input.clone().nth(n)
+    }
+
+    /// Lexes a mixed number like `3½` as `(3 + 1 / 2)`,
+    /// or returns the whole number if no fraction follows it
+    fn lex_mixed_number(&mut self, whole: i64) -> Token {
+        let Some((numerator, denominator)) = self.input.peek().copied().and_then(vulgar_fraction)
+        else {
+            return Token::I64(whole);
+        };
+        self.input.next();
+        self.pending.extend([
+            Token::I64(whole),
+            Token::Op(Op::Plus),
+            Token::I64(numerator),
+            Token::Op(Op::Divide),
+            Token::I64(denominator),
+            Token::RightParen,
+        ]);
+        Token::LeftParen
+    }
+
+    /// Lexes a fraction glyph like `½` as `(1 / 2)`
+    fn lex_fraction(&mut self, c: char) -> Token {
+        let (numerator, denominator) = vulgar_fraction(c).unwrap();
+        self.pending.extend([
+            Token::I64(numerator),
+            Token::Op(Op::Divide),
+            Token::I64(denominator),
+            Token::RightParen,
+        ]);
+        Token::LeftParen
+    }
+
+    /// Lexes a superscript exponent like `²`, `¹⁰` or `⁻¹` as `** 2`, `** 10` or `** -1`
+    fn lex_superscript(&mut self, c: char) -> MLResult<Token> {
+        let mut digits = String::new();
+        match c {
+            '⁻' => self.pending.push_back(Token::Op(Op::Minus)),
+            '⁺' => {}
+            _ => digits.push_str(&superscript_digit(c).unwrap().to_string()),
+        }
+        while let Some(digit) = self.input.peek().copied().and_then(superscript_digit) {
+            digits.push_str(&digit.to_string());
+            self.input.next();
+        }
+        if digits.is_empty() {
+            return Err(MathlineError::InvalidChar(c));
+        }
+        let exponent = digits
+            .parse()
+            .map_err(|_| MathlineError::InvalidNumber(digits))?;
+        self.pending.push_back(Token::I64(exponent));
+        Ok(Token::Op(Op::Exponent))
+    }
+
+    /// Lexes a string literal, after its opening quote
+    fn lex_string(&mut self, quote: char) -> MLResult<Token> {
+        let mut string = String::new();
+        loop {
+            match self.input.next().ok_or(MathlineError::InvalidEOF)? {
+                c if c == quote => return Ok(Token::Str(string)),
+                '\\' => match self.input.next().ok_or(MathlineError::InvalidEOF)? {
+                    'n' => string.push('\n'),
+                    't' => string.push('\t'),
+                    c => string.push(c),
+                },
+                c => string.push(c),
+            }
         }
     }
 
     fn lex_symbol(&mut self, c: char) -> MLResult<Token> {
         let mut symbol = c.to_string();
         while let Some(c) = self.input.peek() {
-            if c.is_ascii_alphanumeric() {
+            // not is_alphanumeric, which would accept superscripts and fraction glyphs
+            if c.is_alphabetic() || c.is_ascii_digit() || *c == '_' {
                 symbol.push(*c);
                 self.input.next();
             } else {
@@ -175,10 +350,236 @@ impl<'a> Lexer<'a> {
         match symbol.as_str() {
             "True" | "true" => Ok(Token::Bool(true)),
             "False" | "false" => Ok(Token::Bool(false)),
+            // python's `not in` is one operator, which `not` alone can't start
+            "not" if self.take_word("in") => Ok(Token::Op(Op::NotIn)),
             "not" => Ok(Token::Op(Op::Not)),
             "and" => Ok(Token::Op(Op::And)),
             "or" => Ok(Token::Op(Op::Or)),
+            "is" => Ok(Token::Op(Op::Is)),
+            "in" => Ok(Token::Op(Op::In)),
             _ => Ok(Token::Symbol(symbol)),
         }
     }
+
+    /// Consumes the word if it comes next, after any whitespace
+    fn take_word(&mut self, word: &str) -> bool {
+        let mut input = self.input.clone();
+        while input.next_if(|c| matches!(c, ' ' | '\t' | '\n')).is_some() {}
+        let mut next = String::new();
+        while let Some(c) = input.next_if(|c| c.is_alphabetic() || c.is_ascii_digit() || *c == '_')
+        {
+            next.push(c);
+        }
+        if next != word {
+            return false;
+        }
+        self.input = input;
+        true
+    }
+}
+
+/// Returns the value of a superscript digit like `²`
+fn superscript_digit(c: char) -> Option<u32> {
+    match c {
+        '⁰' => Some(0),
+        '¹' => Some(1),
+        '²' => Some(2),
+        '³' => Some(3),
+        '⁴'..='⁹' => Some(c as u32 - '⁴' as u32 + 4),
+        _ => None,
+    }
+}
+
+/// Returns the numerator and denominator of a fraction glyph like `½`
+fn vulgar_fraction(c: char) -> Option<(i64, i64)> {
+    Some(match c {
+        '½' => (1, 2),
+        '⅓' => (1, 3),
+        '⅔' => (2, 3),
+        '¼' => (1, 4),
+        '¾' => (3, 4),
+        '⅕' => (1, 5),
+        '⅖' => (2, 5),
+        '⅗' => (3, 5),
+        '⅘' => (4, 5),
+        '⅙' => (1, 6),
+        '⅚' => (5, 6),
+        '⅐' => (1, 7),
+        '⅛' => (1, 8),
+        '⅜' => (3, 8),
+        '⅝' => (5, 8),
+        '⅞' => (7, 8),
+        '⅑' => (1, 9),
+        '⅒' => (1, 10),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use fallible_iterator::FallibleIterator;
+
+    use crate::{
+        error::MathlineError,
+        interpreter::evaluator::Evaluator,
+        parser::{ParseOptions, Parser, lexer::Lexer, token::Token},
+    };
+
+    fn parse(input: &str) -> String {
+        let expressions: Vec<_> = Parser::new(input, ParseOptions::default())
+            .collect()
+            .unwrap_or_else(|error| panic!("failed to parse {input}: {error}"));
+        format!("{expressions:?}")
+    }
+
+    fn eval(input: &str) -> String {
+        let expression = Parser::new(input, ParseOptions::default())
+            .next()
+            .unwrap()
+            .unwrap();
+        Evaluator::new(expression).eval().unwrap().to_string()
+    }
+
+    #[test]
+    fn lexes_numbers() {
+        let lex = |input| Lexer::new(input, ParseOptions::default()).collect::<Vec<_>>();
+        let cases = [
+            ("42", Token::I64(42)),
+            ("1_000_000", Token::I64(1_000_000)),
+            ("0xff", Token::I64(255)),
+            ("0X_FF", Token::I64(255)),
+            ("0o17", Token::I64(15)),
+            ("0b1010_1010", Token::I64(170)),
+            ("2.5", Token::F64(2.5)),
+            (".5", Token::F64(0.5)),
+            ("5.", Token::F64(5.0)),
+            ("1e6", Token::F64(1e6)),
+            ("1_0.0_1E-0_3", Token::F64(10.01e-3)),
+            ("2.5e+3", Token::F64(2500.0)),
+            // correctly rounded, not accumulated digit by digit
+            ("0.1", Token::F64(0.1)),
+            ("2.2250738585072014e-308", Token::F64(f64::MIN_POSITIVE)),
+            ("9007199254740993", Token::I64(9_007_199_254_740_993)),
+            ("9223372036854775808", Token::F64(2f64.powi(63))),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(lex(input).unwrap(), [expected], "lexing {input}");
+        }
+        for input in [
+            "1__0", "1_", "1_000_", "1._5", "0x", "0x__1", "0b12", "0xfg", "1e5_",
+        ] {
+            assert!(
+                matches!(lex(input), Err(MathlineError::InvalidNumber(_))),
+                "lexing {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn bitwise_tokens_print_as_they_lex() {
+        let options = ParseOptions {
+            caret_is_xor: true,
+            ..ParseOptions::default()
+        };
+        let input = "5 ^ 3 & ~1 | 1 << 2 >> 1";
+        let tokens: Vec<_> = Lexer::new(input, options).collect().unwrap();
+        let printed: Vec<_> = tokens.iter().map(ToString::to_string).collect();
+        assert_eq!(printed.join(" "), input.replace("~1", "~ 1"));
+    }
+
+    #[test]
+    fn unicode_parses_like_ascii() {
+        let cases = [
+            ("1 ≠ 2", "1 != 2"),
+            ("1 ≤ 2", "1 <= 2"),
+            ("1 ⩽ 2", "1 <= 2"),
+            ("1 ≥ 2", "1 >= 2"),
+            ("1 ⩾ 2", "1 >= 2"),
+            ("2 × 3", "2 * 3"),
+            ("2 · 3", "2 * 3"),
+            ("2 ⋅ 3", "2 * 3"),
+            ("2 ∗ 3", "2 * 3"),
+            ("6 ÷ 3", "6 / 3"),
+            ("6 ∕ 3", "6 / 3"),
+            ("5 − 3", "5 - 3"),
+            ("−3", "-3"),
+            ("True ∧ False", "True and False"),
+            ("True ∨ False", "True or False"),
+            ("¬True", "not True"),
+            ("x⁰", "x ** 0"),
+            ("x¹", "x ** 1"),
+            ("x²", "x ** 2"),
+            ("x³", "x ** 3"),
+            ("x⁴", "x ** 4"),
+            ("x⁵", "x ** 5"),
+            ("x⁶", "x ** 6"),
+            ("x⁷", "x ** 7"),
+            ("x⁸", "x ** 8"),
+            ("x⁹", "x ** 9"),
+            ("x¹⁰", "x ** 10"),
+            ("x²³", "x ** 23"),
+            ("x⁻¹", "x ** -1"),
+            ("x⁺²", "x ** 2"),
+            ("-x²", "-x ** 2"),
+            ("√16", "sqrt(16)"),
+            ("√(x + 1)", "sqrt(x + 1)"),
+            ("√x * 2", "sqrt(x) * 2"),
+            ("∛27", "cbrt(27)"),
+            ("½", "(1 / 2)"),
+            ("⅓", "(1 / 3)"),
+            ("¾", "(3 / 4)"),
+            ("⅞", "(7 / 8)"),
+            ("⅒", "(1 / 10)"),
+            ("3½", "(3 + 1 / 2)"),
+            ("2 * ½", "2 * (1 / 2)"),
+            ("π", "π"),
+            ("τ / 2", "τ / 2"),
+        ];
+        for (unicode, ascii) in cases {
+            assert_eq!(
+                parse(unicode),
+                parse(ascii),
+                "{unicode} should parse as {ascii}"
+            );
+        }
+    }
+
+    #[test]
+    fn unicode_evaluates() {
+        let cases = [
+            ("3 ≥ 2", "true"),
+            ("3 ≤ 2", "false"),
+            ("2¹⁰", "1024"),
+            ("2⁻¹", "0.50000"),
+            ("√16 × 2", "8.00000"),
+            ("∛27", "3.00000"),
+            ("7 ÷ 2", "3.50000"),
+            ("½ + ¼", "0.75000"),
+            ("2½ × 2", "5.00000"),
+            ("1 ± 2", "(3, -1)"),
+            ("±3", "(3, -3)"),
+            ("τ / 2 == π", "true"),
+            ("φ", "1.61803"),
+            ("∞ > 1e300", "true"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                eval(input),
+                expected,
+                "{input} should evaluate to {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_unicode() {
+        for input in ["⁻", "x⁻", "∑"] {
+            assert!(
+                Parser::new(input, ParseOptions::default())
+                    .collect::<Vec<_>>()
+                    .is_err(),
+                "{input} should not parse"
+            );
+        }
+    }
 }
@@ -1,31 +1,155 @@
 pub mod expression;
+pub mod latex;
 pub mod lexer;
 pub mod token;
 
+use std::str::FromStr;
+
 use fallible_iterator::{FallibleIterator, Peekable};
 
 use self::{
     expression::{
-        Expression, FnExpression, InfixExpression, InfixOp, PrefixExpression, PrefixOp,
-        ValueExpression,
+        ComparisonExpression, Expression, FnExpression, InfixExpression, InfixOp, PrefixExpression,
+        PrefixOp, ValueExpression,
     },
     lexer::Lexer,
-    token::Token,
+    token::{Op, Token},
 };
 use crate::{MLResult, error::MathlineError};
 
+/// The deepest an expression may nest. Evaluating, printing and even dropping an
+/// expression recurse through it, so untrusted input must not be able to go deeper.
+/// A chain like `1 + 2 + 3` nests one level per operator, so this allows sums and
+/// products of a few hundred terms, which still fit a 2MB thread stack in debug builds.
+pub const MAX_DEPTH: usize = 500;
+
+/// The deepest the parsers may recurse, through parentheses, prefix operators and
+/// right-binding operators like `**`. The evaluator recurses through these levels too,
+/// and each takes far more stack than a level of a chain, so this is lower than [MAX_DEPTH].
+pub const MAX_NESTING: usize = 100;
+
 pub struct Parser<'a> {
     lexer: Peekable<Lexer<'a>>,
+    options: ParseOptions,
+    /// How many absolute value bars `|` are open
+    abs_depth: usize,
+    /// How many calls to [Parser::parse_expr] are in progress
+    nesting: usize,
+}
+
+/// Options for the syntax accepted by the parser
+#[derive(Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Treat `^` as bitwise xor, as python does, rather than as exponentiation
+    pub caret_is_xor: bool,
+    pub notation: Notation,
+}
+
+/// The grammar accepted by the parser
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Notation {
+    /// Python expressions
+    #[default]
+    Python,
+    /// Conventional math notation, where `2x` and `3(4 + 5)` are multiplication,
+    /// `|x|` is absolute value, `n!` is factorial and `15%` is a percent
+    Math,
+    /// LaTeX math, like `\frac{3}{4} \cdot \sqrt{16}`
+    Latex,
+    /// LaTeX if the input uses LaTeX commands, otherwise python, falling back to math
+    /// notation when the input doesn't read as python.
+    /// Only [parse] detects the notation; a [Parser] treats this as python.
+    Auto,
+}
+
+impl FromStr for Notation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "python" => Ok(Notation::Python),
+            "math" => Ok(Notation::Math),
+            "latex" => Ok(Notation::Latex),
+            "auto" => Ok(Notation::Auto),
+            _ => Err(format!(
+                "unknown notation {s}, expected python, math, latex or auto"
+            )),
+        }
+    }
+}
+
+/// Parses every expression in the input.
+///
+/// With [Notation::Auto], input with LaTeX commands is parsed as LaTeX, and other input
+/// is parsed as math notation if it isn't valid python or if it only reads as python
+/// as several separate expressions, like `2x`. Separate lines stay separate expressions.
+pub fn parse(input: &str, options: ParseOptions) -> MLResult<Vec<Expression>> {
+    match options.notation {
+        Notation::Latex => return Ok(vec![latex::parse(input)?]),
+        Notation::Auto if latex::is_latex(input) => return Ok(vec![latex::parse(input)?]),
+        Notation::Auto => {}
+        Notation::Python | Notation::Math => return Parser::new(input, options).collect(),
+    }
+    let python = ParseOptions {
+        notation: Notation::Python,
+        ..options
+    };
+    let math = ParseOptions {
+        notation: Notation::Math,
+        ..options
+    };
+    let python_result: MLResult<Vec<_>> = Parser::new(input, python).collect();
+    if let Ok(expressions) = &python_result
+        && expressions.len() <= 1
+    {
+        return python_result;
+    }
+    // math notation would multiply separate lines together, so each line is read on its own
+    if input.trim().contains('\n') {
+        let lines: MLResult<Vec<_>> = input
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| parse(line, options))
+            .collect();
+        if let Ok(lines) = lines {
+            return Ok(lines.into_iter().flatten().collect());
+        }
+    }
+    match Parser::new(input, math).collect::<Vec<_>>() {
+        Ok(math_expressions)
+            if !python_result
+                .as_ref()
+                .is_ok_and(|expressions| expressions.len() <= math_expressions.len()) =>
+        {
+            Ok(math_expressions)
+        }
+        _ => python_result,
+    }
 }
 
 impl<'a> Parser<'a> {
     /// Return a new parser from the input
-    pub fn new(input: &'a str) -> Self {
-        let lexer = Lexer::new(input).peekable();
-        Parser { lexer }
+    pub fn new(input: &'a str, options: ParseOptions) -> Self {
+        let lexer = Lexer::new(input, options).peekable();
+        Parser {
+            lexer,
+            options,
+            abs_depth: 0,
+            nesting: 0,
+        }
     }
 }
 
+/// Math notation operators that aren't python infix operators
+enum MathOp {
+    Factorial,
+    Percent,
+    /// Multiplication written as `of`, as in `15% of 200`
+    Of,
+    ImplicitMultiply,
+    CloseAbs,
+}
+
 impl<'a> FallibleIterator for Parser<'a> {
     type Item = Expression;
     type Error = MathlineError;
@@ -39,8 +163,27 @@ impl<'a> FallibleIterator for Parser<'a> {
     }
 }
 
+/// Returns the expression, or an error if it is deeper than [MAX_DEPTH].
+/// Checking each expression as it is built keeps every subexpression within the limit.
+pub(crate) fn check_depth(expr: Expression) -> MLResult<Expression> {
+    if expr.is_deeper_than(MAX_DEPTH) {
+        return Err(MathlineError::NestedTooDeeply(MAX_DEPTH));
+    }
+    Ok(expr)
+}
+
 impl<'a> Parser<'a> {
     fn parse_expr(&mut self, min_precedence: u8) -> MLResult<Expression> {
+        if self.nesting >= MAX_NESTING {
+            return Err(MathlineError::NestedTooDeeply(MAX_NESTING));
+        }
+        self.nesting += 1;
+        let result = self.parse_operators(min_precedence);
+        self.nesting -= 1;
+        result
+    }
+
+    fn parse_operators(&mut self, min_precedence: u8) -> MLResult<Expression> {
         let Some(token) = self.lexer.next()? else {
             return Err(MathlineError::InvalidEOF);
         };
@@ -57,10 +200,17 @@ impl<'a> Parser<'a> {
             Token::Bool(b) => Expression::Value(ValueExpression::Bool(b)),
             Token::I64(n) => Expression::Value(ValueExpression::I64(n)),
             Token::F64(n) => Expression::Value(ValueExpression::F64(n)),
-            Token::LeftParen => {
-                let lhs = self.parse_expr(0)?;
-                self.expect(Token::RightParen)?;
-                lhs
+            Token::Str(s) => Expression::Value(ValueExpression::Str(s)),
+            Token::LeftParen => self.parse_group()?,
+            Token::Op(Op::BitOr) if self.is_math() => self.parse_abs()?,
+            Token::Op(op @ (Op::Sqrt | Op::Cbrt)) => {
+                // `√x` is shorthand for `sqrt(x)` and binds like a prefix op
+                let name = if op == Op::Sqrt { "sqrt" } else { "cbrt" };
+                let parameter = self.parse_expr(PrefixOp::Plus.precedence())?;
+                Expression::Fn(FnExpression {
+                    name: name.to_string(),
+                    parameters: vec![parameter],
+                })
             }
             Token::Op(op) => {
                 let op = PrefixOp::try_from(op)?;
@@ -73,23 +223,190 @@ impl<'a> Parser<'a> {
                 return Err(MathlineError::InvalidSyntax);
             }
         };
-        while let Some(Token::Op(op)) = self.lexer.peek()? {
+        lhs = check_depth(lhs)?;
+        loop {
+            if let Some(math_op) = self.peek_math_op()? {
+                let precedence = match math_op {
+                    MathOp::Factorial | MathOp::Percent => POSTFIX_PRECEDENCE,
+                    MathOp::Of | MathOp::ImplicitMultiply => InfixOp::Multiply.precedence().0,
+                    MathOp::CloseAbs => break,
+                };
+                if precedence < min_precedence {
+                    break;
+                }
+                lhs = check_depth(self.parse_math_op(math_op, lhs)?)?;
+                continue;
+            }
+            let Some(Token::Op(op)) = self.lexer.peek()? else {
+                break;
+            };
             let op = InfixOp::try_from(*op)?;
             let (lhs_precedence, rhs_precedence) = op.precedence();
             if lhs_precedence < min_precedence {
                 break;
             }
+            if op.is_comparison() {
+                lhs = check_depth(self.parse_comparison(lhs)?)?;
+                continue;
+            }
             self.lexer.next()?;
             let rhs = self.parse_expr(rhs_precedence)?;
-            lhs = Expression::Infix(InfixExpression {
+            lhs = check_depth(Expression::Infix(InfixExpression {
                 op,
                 lhs: Box::new(lhs),
                 rhs: Box::new(rhs),
-            });
+            }))?;
         }
         Ok(lhs)
     }
 
+    fn is_math(&self) -> bool {
+        self.options.notation == Notation::Math
+    }
+
+    /// Returns the math notation operator that follows, if any
+    fn peek_math_op(&mut self) -> MLResult<Option<MathOp>> {
+        if !self.is_math() {
+            return Ok(None);
+        }
+        let abs_depth = self.abs_depth;
+        Ok(match self.lexer.peek()? {
+            Some(Token::Op(Op::Not)) => Some(MathOp::Factorial),
+            Some(Token::Op(Op::Modulo)) => Some(MathOp::Percent),
+            Some(Token::Op(Op::BitOr)) if abs_depth > 0 => Some(MathOp::CloseAbs),
+            Some(Token::Symbol(symbol)) if symbol == "of" => Some(MathOp::Of),
+            // an operand directly after another operand, as in `2x`, `3(4 + 5)` or `2|x|`
+            Some(
+                Token::I64(_)
+                | Token::F64(_)
+                | Token::Symbol(_)
+                | Token::LeftParen
+                | Token::Op(Op::Sqrt | Op::Cbrt | Op::BitOr),
+            ) => Some(MathOp::ImplicitMultiply),
+            _ => None,
+        })
+    }
+
+    fn parse_math_op(&mut self, math_op: MathOp, lhs: Expression) -> MLResult<Expression> {
+        Ok(match math_op {
+            MathOp::Factorial => {
+                self.lexer.next()?;
+                Expression::Fn(FnExpression {
+                    name: "factorial".to_string(),
+                    parameters: vec![lhs],
+                })
+            }
+            MathOp::Percent => {
+                self.lexer.next()?;
+                Expression::Infix(InfixExpression {
+                    op: InfixOp::Divide,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(Expression::Value(ValueExpression::I64(100))),
+                })
+            }
+            MathOp::Of | MathOp::ImplicitMultiply => {
+                if let MathOp::Of = math_op {
+                    self.lexer.next()?;
+                }
+                let (_, rhs_precedence) = InfixOp::Multiply.precedence();
+                let rhs = self.parse_expr(rhs_precedence)?;
+                Expression::Infix(InfixExpression {
+                    op: InfixOp::Multiply,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                })
+            }
+            MathOp::CloseAbs => return Err(MathlineError::InvalidSyntax),
+        })
+    }
+
+    /// Parses `|x|` as `abs(x)`, after the opening bar
+    fn parse_abs(&mut self) -> MLResult<Expression> {
+        self.abs_depth += 1;
+        let parameter = self.parse_expr(0);
+        self.abs_depth -= 1;
+        let parameter = parameter?;
+        if self.lexer.next()? != Some(Token::Op(Op::BitOr)) {
+            return Err(MathlineError::InvalidSyntax);
+        }
+        Ok(Expression::Fn(FnExpression {
+            name: "abs".to_string(),
+            parameters: vec![parameter],
+        }))
+    }
+
+    /// Parses a chain of comparisons following `lhs`.
+    /// Python treats `a < b < c` as `a < b and b < c`, not `(a < b) < c`.
+    fn parse_comparison(&mut self, lhs: Expression) -> MLResult<Expression> {
+        let mut comparisons = vec![];
+        while let Some(mut op) = self.peek_comparison()? {
+            self.lexer.next()?;
+            if let InfixOp::Is = op
+                && let Some(Token::Op(Op::Not)) = self.lexer.peek()?
+            {
+                self.lexer.next()?;
+                op = InfixOp::IsNot;
+            }
+            let (_, rhs_precedence) = op.precedence();
+            let rhs = self.parse_expr(rhs_precedence)?;
+            comparisons.push((op, rhs));
+        }
+
+        if comparisons.len() == 1 {
+            let (op, rhs) = comparisons.pop().unwrap();
+            return Ok(Expression::Infix(InfixExpression {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            }));
+        }
+
+        Ok(Expression::Comparison(ComparisonExpression {
+            lhs: Box::new(lhs),
+            comparisons,
+        }))
+    }
+
+    fn peek_comparison(&mut self) -> MLResult<Option<InfixOp>> {
+        if let Some(Token::Op(op)) = self.lexer.peek()?
+            && let Ok(op) = InfixOp::try_from(*op)
+            && op.is_comparison()
+        {
+            Ok(Some(op))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Parses a parenthesized expression or a tuple, after the left paren
+    fn parse_group(&mut self) -> MLResult<Expression> {
+        let mut exprs = vec![];
+        let mut is_tuple = false;
+        loop {
+            // `()` is an empty tuple, and `(1,)` may end in a trailing comma
+            if let Some(Token::RightParen) = self.lexer.peek()? {
+                self.lexer.next()?;
+                is_tuple = true;
+                break;
+            }
+            exprs.push(self.parse_expr(0)?);
+            let token = self
+                .lexer
+                .next()?
+                .ok_or_else(|| MathlineError::InvalidEOF)?;
+            match token {
+                Token::RightParen => break,
+                Token::Comma => is_tuple = true,
+                _ => return Err(MathlineError::InvalidSyntax),
+            }
+        }
+        if is_tuple {
+            Ok(Expression::Tuple(exprs))
+        } else {
+            Ok(exprs.pop().unwrap())
+        }
+    }
+
     fn parse_fn(&mut self, name: String) -> MLResult<FnExpression> {
         let mut parameters = vec![];
 
@@ -114,30 +431,117 @@ impl<'a> Parser<'a> {
 
         Ok(FnExpression { name, parameters })
     }
+}
 
-    fn expect(&mut self, token: Token) -> MLResult<()> {
-        let t = self.lexer.next()?;
-        if t == Some(token) {
-            Ok(())
-        } else {
-            Err(MathlineError::InvalidSyntax)
+impl Expression {
+    /// How tightly the expression holds onto operators on its left and right,
+    /// on the scale of [InfixOp::precedence]. Atoms like `2` or `f(x)` bind tightest.
+    pub(crate) fn binding(&self) -> (u8, u8) {
+        match self {
+            // an operand printed without parens passes on its own binding,
+            // so `a + not b` binds as loosely as `not` on the right
+            Expression::Infix(expr) => {
+                let (lhs, rhs) = expr.op.precedence();
+                let lhs_binding = expr.lhs.binding();
+                let rhs_binding = expr.rhs.binding();
+                let lhs = if expr.op.lhs_binding_needs_parens(&expr.lhs, lhs_binding) {
+                    lhs
+                } else {
+                    lhs.min(lhs_binding.0)
+                };
+                let rhs = if expr.op.rhs_binding_needs_parens(&expr.rhs, rhs_binding) {
+                    rhs
+                } else {
+                    rhs.min(rhs_binding.1)
+                };
+                (lhs, rhs)
+            }
+            Expression::Comparison(expr) => {
+                let (first_op, _) = &expr.comparisons[0];
+                let (last_op, last) = expr.comparisons.last().unwrap();
+                let (lhs, _) = first_op.precedence();
+                let (_, rhs) = last_op.precedence();
+                let lhs_binding = expr.lhs.binding();
+                let rhs_binding = last.binding();
+                let lhs = if first_op.lhs_binding_needs_parens(&expr.lhs, lhs_binding) {
+                    lhs
+                } else {
+                    lhs.min(lhs_binding.0)
+                };
+                let rhs = if last_op.rhs_binding_needs_parens(last, rhs_binding) {
+                    rhs
+                } else {
+                    rhs.min(rhs_binding.1)
+                };
+                (lhs, rhs)
+            }
+            // nothing binds to the left of a prefix op, so `2 ** -1` needs no parens
+            Expression::Prefix(expr) => {
+                let precedence = expr.op.precedence();
+                let rhs_binding = expr.rhs.binding();
+                if rhs_binding.0 < precedence {
+                    (u8::MAX, precedence)
+                } else {
+                    (u8::MAX, precedence.min(rhs_binding.1))
+                }
+            }
+            Expression::Value(ValueExpression::I64(n)) if *n < 0 => {
+                (u8::MAX, PrefixOp::Minus.precedence())
+            }
+            Expression::Value(ValueExpression::F64(n)) if n.is_sign_negative() => {
+                (u8::MAX, PrefixOp::Minus.precedence())
+            }
+            Expression::Value(_) | Expression::Fn(_) | Expression::Tuple(_) => (u8::MAX, u8::MAX),
+            Expression::Series(_) => (u8::MAX, u8::MAX),
+        }
+    }
+
+    fn is_comparison(&self) -> bool {
+        match self {
+            Expression::Infix(expr) => expr.op.is_comparison(),
+            Expression::Comparison(_) => true,
+            _ => false,
         }
     }
 }
 
 impl PrefixOp {
-    fn precedence(&self) -> u8 {
+    /// Returns true if `rhs` must be parenthesized to print as this operator's operand
+    pub(crate) fn rhs_needs_parens(&self, rhs: &Expression) -> bool {
+        let (rhs_binding, _) = rhs.binding();
+        rhs_binding < self.precedence()
+    }
+}
+
+impl ComparisonExpression {
+    /// Returns true if the operand of the `i`th comparison must be parenthesized,
+    /// which includes when it would swallow the next comparison, as `not b` does in `a < not b < c`
+    pub(crate) fn rhs_needs_parens(&self, i: usize) -> bool {
+        let (op, rhs) = &self.comparisons[i];
+        op.rhs_needs_parens(rhs)
+            || self
+                .comparisons
+                .get(i + 1)
+                .is_some_and(|(next_op, _)| next_op.lhs_needs_parens(rhs))
+    }
+}
+
+/// Math notation's postfix `n!` and `15%` bind tighter than any other operator
+const POSTFIX_PRECEDENCE: u8 = 27;
+
+impl PrefixOp {
+    pub(crate) fn precedence(&self) -> u8 {
         // follow python operator precedence
         // https://docs.python.org/3/reference/expressions.html#operator-precedence
         match self {
             PrefixOp::Not => 5,
-            PrefixOp::Plus | PrefixOp::Minus => 15,
+            PrefixOp::Plus | PrefixOp::Minus | PrefixOp::PlusMinus | PrefixOp::BitNot => 23,
         }
     }
 }
 
 impl InfixOp {
-    fn precedence(&self) -> (u8, u8) {
+    pub(crate) fn precedence(&self) -> (u8, u8) {
         // follow python operator precedence
         // https://docs.python.org/3/reference/expressions.html#operator-precedence
         match self {
@@ -148,11 +552,178 @@ impl InfixOp {
             | InfixOp::LessThan
             | InfixOp::LessThanOrEqual
             | InfixOp::GreaterThan
-            | InfixOp::GreaterThanOrEqual => (7, 8),
-            InfixOp::Add | InfixOp::Subtract => (11, 12),
-            InfixOp::Multiply | InfixOp::Divide | InfixOp::Modulo => (13, 14),
-            InfixOp::Exponent => (15, 16),
-            InfixOp::Dot => (18, 17), // right associative
+            | InfixOp::GreaterThanOrEqual
+            | InfixOp::Is
+            | InfixOp::IsNot
+            | InfixOp::In
+            | InfixOp::NotIn => (7, 8),
+            InfixOp::BitOr => (9, 10),
+            InfixOp::BitXor => (11, 12),
+            InfixOp::BitAnd => (13, 14),
+            InfixOp::ShiftLeft | InfixOp::ShiftRight => (15, 16),
+            InfixOp::Add | InfixOp::Subtract | InfixOp::PlusMinus => (17, 18),
+            InfixOp::Multiply | InfixOp::Divide | InfixOp::Modulo => (19, 20),
+            InfixOp::Exponent => (24, 23), // right associative
+            InfixOp::Dot => (26, 25),      // right associative
+        }
+    }
+
+    /// Returns true if `lhs` must be parenthesized to print as this operator's lhs
+    pub(crate) fn lhs_needs_parens(&self, lhs: &Expression) -> bool {
+        self.lhs_binding_needs_parens(lhs, lhs.binding())
+    }
+
+    fn lhs_binding_needs_parens(&self, lhs: &Expression, (_, lhs_binding): (u8, u8)) -> bool {
+        if self.is_comparison() && lhs.is_comparison() {
+            // otherwise `(a < b) < c` would print as the chain `a < b < c`
+            return true;
+        }
+        lhs_binding <= self.precedence().0
+    }
+
+    /// Returns true if `rhs` must be parenthesized to print as this operator's rhs
+    pub(crate) fn rhs_needs_parens(&self, rhs: &Expression) -> bool {
+        self.rhs_binding_needs_parens(rhs, rhs.binding())
+    }
+
+    fn rhs_binding_needs_parens(&self, rhs: &Expression, (rhs_binding, _): (u8, u8)) -> bool {
+        if self.is_comparison() && rhs.is_comparison() {
+            return true;
+        }
+        if let (InfixOp::Is, Expression::Prefix(rhs)) = (self, rhs)
+            && let PrefixOp::Not = rhs.op
+        {
+            // otherwise `a is (not b)` would print as `a is not b`
+            return true;
+        }
+        rhs_binding < self.precedence().1
+    }
+
+    pub(crate) fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            InfixOp::Equal
+                | InfixOp::NotEqual
+                | InfixOp::LessThan
+                | InfixOp::LessThanOrEqual
+                | InfixOp::GreaterThan
+                | InfixOp::GreaterThanOrEqual
+                | InfixOp::Is
+                | InfixOp::IsNot
+                | InfixOp::In
+                | InfixOp::NotIn
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::evaluator::Evaluator;
+
+    #[test]
+    fn keeps_lines_apart_in_auto_notation() {
+        let options = ParseOptions {
+            notation: Notation::Auto,
+            ..ParseOptions::default()
+        };
+        let cases: [(&str, &[&str]); 4] = [
+            ("3 + 4\n5 * 6", &["3 + 4", "5 * 6"]),
+            ("x = 5\nx * 2", &["x == 5", "x * 2"]),
+            ("2x\n\n3y", &["2 * x", "3 * y"]),
+            ("(1 +\n2) * 3", &["(1 + 2) * 3"]),
+        ];
+        for (input, expected) in cases {
+            let exprs: Vec<_> = parse(input, options)
+                .unwrap()
+                .iter()
+                .map(Expression::to_string)
+                .collect();
+            assert_eq!(exprs, expected, "parsing {input:?}");
         }
     }
+
+    #[test]
+    fn parses_math_notation() {
+        let options = ParseOptions {
+            notation: Notation::Math,
+            ..ParseOptions::default()
+        };
+        let cases = [
+            ("2x^2", "2 * x ** 2"),
+            ("-2x", "-2 * x"),
+            ("2^3x", "2 ** 3 * x"),
+            ("3(4 + 5)", "3 * (4 + 5)"),
+            ("(1 + 2)(3 + 4)", "(1 + 2) * (3 + 4)"),
+            ("2pi r", "2 * pi * r"),
+            ("50% of 80", "50 / 100 * 80"),
+            ("50%", "50 / 100"),
+            ("3!^2", "factorial(3) ** 2"),
+            ("2 * 3!", "2 * factorial(3)"),
+            ("|a-b|c", "abs(a - b) * c"),
+            ("|-3| + |2|", "abs(-3) + abs(2)"),
+            // implicit multiplication binds no tighter than `*`
+            ("6 / 2x", "6 / 2 * x"),
+        ];
+        for (input, expected) in cases {
+            let expr = parse(input, options).unwrap().remove(0);
+            assert_eq!(expr.to_string(), expected, "parsing {input}");
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let deep = [
+            (Notation::Python, "(".repeat(30_000)),
+            (
+                Notation::Python,
+                format!(
+                    "{}1{}",
+                    "(".repeat(MAX_NESTING + 1),
+                    ")".repeat(MAX_NESTING + 1)
+                ),
+            ),
+            (Notation::Python, format!("{}1", "-".repeat(30_000))),
+            (Notation::Python, format!("{}2", "2**".repeat(30_000))),
+            (Notation::Python, format!("1{}", " + 1".repeat(30_000))),
+            (Notation::Math, format!("{}1", "|".repeat(30_000))),
+            (Notation::Math, format!("1{}", "!".repeat(30_000))),
+            (Notation::Math, format!("2{}", " x".repeat(30_000))),
+            (Notation::Latex, "(".repeat(30_000)),
+            (
+                Notation::Latex,
+                format!("{}1{}", "\\sqrt{".repeat(30_000), "}".repeat(30_000)),
+            ),
+            (Notation::Latex, format!("{}1", "\\frac{1}".repeat(30_000))),
+            (Notation::Latex, format!("{}1", "-".repeat(30_000))),
+            (Notation::Latex, format!("1{}", "!".repeat(30_000))),
+            (Notation::Latex, format!("2{}", "x".repeat(30_000))),
+            (Notation::Latex, format!("1{}", " + 1".repeat(30_000))),
+        ];
+        for (notation, input) in deep {
+            let options = ParseOptions {
+                notation,
+                ..ParseOptions::default()
+            };
+            assert!(
+                matches!(
+                    parse(&input, options),
+                    Err(MathlineError::NestedTooDeeply(_))
+                ),
+                "parsing {}...",
+                &input[..20]
+            );
+        }
+
+        // anything shallower still parses and evaluates
+        let depth = MAX_NESTING / 3;
+        let nested = format!("{}1{}", "(1 + ".repeat(depth), ")".repeat(depth));
+        let expr = parse(&nested, ParseOptions::default()).unwrap().remove(0);
+        assert_eq!(Evaluator::new(expr).eval().unwrap().to_string(), "34");
+
+        // a long sum is a deep expression, but doesn't recurse in the parser
+        let sum = format!("1{}", " + 1".repeat(MAX_DEPTH - 1));
+        let expr = parse(&sum, ParseOptions::default()).unwrap().remove(0);
+        assert_eq!(Evaluator::new(expr).eval().unwrap().to_string(), "500");
+    }
 }
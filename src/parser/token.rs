@@ -1,10 +1,11 @@
 use std::fmt::Display;
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum Token {
     Bool(bool),
     I64(i64),
     F64(f64),
+    Str(String),
     Symbol(String),
     Op(Op),
     LeftParen,
@@ -12,15 +13,24 @@ pub enum Token {
     Comma,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Op {
     Not,
     Plus,
     Minus,
+    PlusMinus,
     Multiply,
     Divide,
     Modulo,
     Exponent,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
+    Sqrt,
+    Cbrt,
     And,
     Or,
     Equal,
@@ -29,6 +39,9 @@ pub enum Op {
     LessThanOrEqual,
     GreaterThan,
     GreaterThanOrEqual,
+    Is,
+    In,
+    NotIn,
     Dot,
 }
 
@@ -38,6 +51,7 @@ impl Display for Token {
             Token::Bool(bool) => bool.fmt(f),
             Token::I64(num) => num.fmt(f),
             Token::F64(num) => num.fmt(f),
+            Token::Str(s) => write!(f, "{s:?}"),
             Token::Symbol(symbol) => symbol.fmt(f),
             Token::Op(op) => op.fmt(f),
             Token::LeftParen => write!(f, "("),
@@ -53,10 +67,19 @@ impl Display for Op {
             Op::Not => write!(f, "not"),
             Op::Plus => write!(f, "+"),
             Op::Minus => write!(f, "-"),
+            Op::PlusMinus => write!(f, "±"),
             Op::Multiply => write!(f, "*"),
             Op::Divide => write!(f, "/"),
             Op::Modulo => write!(f, "%"),
             Op::Exponent => write!(f, "^"),
+            Op::BitAnd => write!(f, "&"),
+            Op::BitOr => write!(f, "|"),
+            Op::BitXor => write!(f, "^"),
+            Op::BitNot => write!(f, "~"),
+            Op::ShiftLeft => write!(f, "<<"),
+            Op::ShiftRight => write!(f, ">>"),
+            Op::Sqrt => write!(f, "√"),
+            Op::Cbrt => write!(f, "∛"),
             Op::Dot => write!(f, "."),
             Op::And => write!(f, "and"),
             Op::Or => write!(f, "or"),
@@ -66,6 +89,9 @@ impl Display for Op {
             Op::LessThanOrEqual => write!(f, "≤"),
             Op::GreaterThan => write!(f, ">"),
             Op::GreaterThanOrEqual => write!(f, "≥"),
+            Op::Is => write!(f, "is"),
+            Op::In => write!(f, "in"),
+            Op::NotIn => write!(f, "not in"),
         }
     }
 }
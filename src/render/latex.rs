@@ -0,0 +1,316 @@
+use crate::{
+    interpreter::{evaluator::Step, value::Value},
+    parser::expression::{
+        Expression, InfixExpression, InfixOp, PrefixOp, SeriesExpression, SeriesOp, ValueExpression,
+    },
+    render::{float, function_word, is_scientific, variable_symbol},
+};
+
+/// Renders the expression as LaTeX math, like `\frac{3}{4} \cdot \sqrt{16}`
+pub fn expression(expr: &Expression) -> String {
+    match expr {
+        Expression::Value(expr) => value_expression(expr),
+        Expression::Fn(expr) => function(&expr.name, &expr.parameters),
+        Expression::Infix(expr) => infix(expr),
+        Expression::Prefix(expr) => {
+            let op = match expr.op {
+                PrefixOp::Not => "\\lnot ",
+                PrefixOp::Plus => "+",
+                PrefixOp::Minus => "-",
+                PrefixOp::PlusMinus => "\\pm ",
+                PrefixOp::BitNot => "\\sim ",
+            };
+            let rhs = operand(&expr.rhs, expr.op.rhs_needs_parens(&expr.rhs));
+            format!("{op}{rhs}")
+        }
+        Expression::Comparison(expr) => {
+            let (first_op, _) = &expr.comparisons[0];
+            let mut latex = operand(&expr.lhs, first_op.lhs_needs_parens(&expr.lhs));
+            for (i, (op, rhs)) in expr.comparisons.iter().enumerate() {
+                let rhs = operand(rhs, expr.rhs_needs_parens(i));
+                latex.push_str(&format!(" {} {rhs}", infix_op(op)));
+            }
+            latex
+        }
+        Expression::Tuple(exprs) => {
+            let exprs: Vec<_> = exprs.iter().map(expression).collect();
+            parens(&exprs.join(", "))
+        }
+        Expression::Series(expr) => series(expr),
+    }
+}
+
+/// Renders an evaluation step as `expression \Rightarrow value`
+pub fn step(step: &Step) -> String {
+    format!(
+        "{} \\Rightarrow {}",
+        expression(&step.expression),
+        value(&step.value)
+    )
+}
+
+/// Renders an evaluated value
+pub fn value(value: &Value) -> String {
+    match value {
+        Value::F64(n) if n.is_nan() => "\\mathrm{NaN}".to_string(),
+        Value::F64(n) if n.is_infinite() => {
+            let sign = if *n < 0.0 { "-" } else { "" };
+            format!("{sign}\\infty")
+        }
+        Value::I64(n) => n.to_string(),
+        Value::F64(n) => scientific(*n, Some(5)),
+        Value::Bool(b) => boolean(*b),
+        Value::Str(s) => string(s),
+        Value::Tuple(values) => {
+            let values: Vec<_> = values.iter().map(self::value).collect();
+            parens(&values.join(", "))
+        }
+        Value::Module(m) => format!("\\mathrm{{{}}}", escape(m)),
+    }
+}
+
+fn value_expression(expr: &ValueExpression) -> String {
+    match expr {
+        ValueExpression::Bool(b) => boolean(*b),
+        ValueExpression::I64(n) => n.to_string(),
+        ValueExpression::F64(n) => scientific(*n, None),
+        ValueExpression::Str(s) => string(s),
+        ValueExpression::Variable(v) => variable(v),
+    }
+}
+
+/// Writes a float, as `m \times 10^{e}` when it's too large or small to write out
+fn scientific(n: f64, decimals: Option<usize>) -> String {
+    match float(n, decimals) {
+        (mantissa, Some(exponent)) => format!("{mantissa} \\times 10^{{{exponent}}}"),
+        (n, None) => n,
+    }
+}
+
+fn variable(name: &str) -> String {
+    if let Some(symbol) = variable_symbol(name) {
+        return match symbol {
+            "π" => "\\pi",
+            "τ" => "\\tau",
+            "φ" => "\\varphi",
+            _ => "\\infty",
+        }
+        .to_string();
+    }
+    // a subscripted variable like `x_1`
+    if let Some((base, subscript)) = name.split_once('_')
+        && base.chars().count() == 1
+        && !subscript.is_empty()
+    {
+        return format!("{base}_{{{}}}", variable(subscript));
+    }
+    if name.chars().count() == 1 {
+        name.to_string()
+    } else {
+        format!("\\mathrm{{{}}}", escape(name))
+    }
+}
+
+fn infix(expr: &InfixExpression) -> String {
+    let lhs = &expr.lhs;
+    let rhs = &expr.rhs;
+    match expr.op {
+        InfixOp::Divide => format!("\\frac{{{}}}{{{}}}", expression(lhs), expression(rhs)),
+        InfixOp::Exponent => {
+            // a fraction or `m × 10^e` base is parenthesized to show all of it is raised
+            let base_parens =
+                expr.op.lhs_needs_parens(lhs) || is_fraction(lhs) || is_scientific(lhs);
+            format!("{}^{{{}}}", operand(lhs, base_parens), expression(rhs))
+        }
+        InfixOp::Dot => match (lhs.as_ref(), rhs.as_ref()) {
+            // drop the module from `math.sin(x)`
+            (Expression::Value(ValueExpression::Variable(module)), _) if module == "math" => {
+                expression(rhs)
+            }
+            // render a method call like `n.bit_count()` as `bit_count(n)`
+            (_, Expression::Fn(method)) => {
+                let mut parameters = vec![lhs.as_ref().clone()];
+                parameters.extend(method.parameters.iter().cloned());
+                function(&method.name, &parameters)
+            }
+            _ => format!("{}.{}", expression(lhs), expression(rhs)),
+        },
+        _ => {
+            // fractions are already grouped, so they never need parens
+            let lhs_parens = expr.op.lhs_needs_parens(lhs) && !is_fraction(lhs);
+            let rhs_parens = expr.op.rhs_needs_parens(rhs) && !is_fraction(rhs);
+            format!(
+                "{} {} {}",
+                operand(lhs, lhs_parens),
+                infix_op(&expr.op),
+                operand(rhs, rhs_parens)
+            )
+        }
+    }
+}
+
+fn infix_op(op: &InfixOp) -> &'static str {
+    match op {
+        InfixOp::Add => "+",
+        InfixOp::Subtract => "-",
+        InfixOp::PlusMinus => "\\pm",
+        InfixOp::Multiply => "\\cdot",
+        InfixOp::Divide => "/",
+        InfixOp::Modulo => "\\bmod",
+        InfixOp::Exponent => "^",
+        InfixOp::BitAnd => "\\mathbin{\\&}",
+        InfixOp::BitOr => "\\mathbin{|}",
+        InfixOp::BitXor => "\\oplus",
+        InfixOp::ShiftLeft => "\\ll",
+        InfixOp::ShiftRight => "\\gg",
+        InfixOp::And => "\\land",
+        InfixOp::Or => "\\lor",
+        InfixOp::Equal => "=",
+        InfixOp::NotEqual => "\\neq",
+        InfixOp::LessThan => "<",
+        InfixOp::LessThanOrEqual => "\\leq",
+        InfixOp::GreaterThan => ">",
+        InfixOp::GreaterThanOrEqual => "\\geq",
+        InfixOp::Is => "\\mathrel{\\mathrm{is}}",
+        InfixOp::IsNot => "\\mathrel{\\mathrm{is\\ not}}",
+        InfixOp::In => "\\in",
+        InfixOp::NotIn => "\\notin",
+        InfixOp::Dot => ".",
+    }
+}
+
+fn function(name: &str, parameters: &[Expression]) -> String {
+    match (name, parameters) {
+        ("sqrt", [x]) => format!("\\sqrt{{{}}}", expression(x)),
+        ("cbrt", [x]) => format!("\\sqrt[3]{{{}}}", expression(x)),
+        ("abs", [x]) => format!("\\left|{}\\right|", expression(x)),
+        ("factorial", [x]) => {
+            let (_, binding) = x.binding();
+            format!("{}!", operand(x, binding < u8::MAX))
+        }
+        // the evaluator's one argument log is base 10
+        ("log", [x]) => format!("\\log_{{10}}{}", parens(&expression(x))),
+        ("log", [x, base]) => format!("\\log_{{{}}}{}", expression(base), parens(&expression(x))),
+        _ => {
+            let parameters: Vec<_> = parameters.iter().map(expression).collect();
+            let parameters = parens(&parameters.join(", "));
+            match function_word(name) {
+                Some(word) => format!("\\{word}{parameters}"),
+                None => format!("\\operatorname{{{}}}{parameters}", escape(name)),
+            }
+        }
+    }
+}
+
+fn series(expr: &SeriesExpression) -> String {
+    let op = match expr.op {
+        SeriesOp::Sum => "\\sum",
+        SeriesOp::Product => "\\prod",
+    };
+    // the body of a series extends over one term, so a sum needs parens
+    let body_parens = expr.body.binding().0 < InfixOp::Multiply.precedence().0;
+    format!(
+        "{op}_{{{}={}}}^{{{}}} {}",
+        variable(&expr.variable),
+        expression(&expr.lower),
+        expression(&expr.upper),
+        operand(&expr.body, body_parens)
+    )
+}
+
+fn operand(expr: &Expression, needs_parens: bool) -> String {
+    if needs_parens {
+        parens(&expression(expr))
+    } else {
+        expression(expr)
+    }
+}
+
+fn parens(latex: &str) -> String {
+    format!("\\left({latex}\\right)")
+}
+
+fn is_fraction(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::Infix(InfixExpression {
+            op: InfixOp::Divide,
+            ..
+        })
+    )
+}
+
+fn boolean(b: bool) -> String {
+    if b {
+        "\\mathrm{True}".to_string()
+    } else {
+        "\\mathrm{False}".to_string()
+    }
+}
+
+fn string(s: &str) -> String {
+    format!("\\text{{`{}'}}", escape(s))
+}
+
+/// Escapes characters with special meaning in LaTeX text
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '_' | '&' | '%' | '$' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '^' => escaped.push_str("\\^{}"),
+            '~' => escaped.push_str("\\~{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        interpreter::value::Value,
+        parser::{self, ParseOptions},
+    };
+
+    #[test]
+    fn renders_expressions() {
+        let cases = [
+            ("3 / 4 * sqrt(16)", r"\frac{3}{4} \cdot \sqrt{16}"),
+            ("2 ** (x + 1)", "2^{x + 1}"),
+            ("-(1 + 2)", r"-\left(1 + 2\right)"),
+            ("abs(a - b)", r"\left|a - b\right|"),
+            ("1 < x <= 10", r"1 < x \leq 10"),
+            ("3 in (1, 2, 3)", r"3 \in \left(1, 2, 3\right)"),
+            ("pi * x_1", r"\pi \cdot x_{1}"),
+            ("0.25", "0.25"),
+            ("1.5e300", r"1.5 \times 10^{300}"),
+            ("-2.5e-7", r"-2.5 \times 10^{-7}"),
+            ("1e20 ** 2", r"\left(1 \times 10^{20}\right)^{2}"),
+        ];
+        for (input, expected) in cases {
+            let expr = parser::parse(input, ParseOptions::default())
+                .unwrap()
+                .remove(0);
+            assert_eq!(super::expression(&expr), expected, "rendering {input}");
+        }
+    }
+
+    #[test]
+    fn renders_values() {
+        let cases = [
+            (Value::F64(0.5), "0.50000"),
+            (Value::F64(1.5e300), r"1.50000 \times 10^{300}"),
+            (Value::F64(1e-5), r"1.00000 \times 10^{-5}"),
+            (Value::I64(-3), "-3"),
+            (Value::F64(f64::NEG_INFINITY), r"-\infty"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(super::value(&value), expected);
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    interpreter::{evaluator::Step, value::Value},
+    parser::expression::{
+        Expression, InfixExpression, InfixOp, PrefixOp, SeriesExpression, SeriesOp, ValueExpression,
+    },
+    render::{float, function_word, is_scientific, variable_symbol},
+};
+
+/// Renders the expression as a MathML `<math>` element
+pub fn expression(expr: &Expression) -> String {
+    math(&node(expr))
+}
+
+/// Renders an evaluation step as `expression ⇒ value` in a MathML `<math>` element
+pub fn step(step: &Step) -> String {
+    math(&format!(
+        "{}<mo>⇒</mo>{}",
+        node(&step.expression),
+        value(&step.value)
+    ))
+}
+
+fn math(content: &str) -> String {
+    format!("<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mrow>{content}</mrow></math>")
+}
+
+fn value(value: &Value) -> String {
+    match value {
+        Value::F64(n) if n.is_nan() => "<mi>NaN</mi>".to_string(),
+        Value::F64(n) if n.is_infinite() => {
+            let sign = if *n < 0.0 { "<mo>-</mo>" } else { "" };
+            format!("<mrow>{sign}<mi>∞</mi></mrow>")
+        }
+        Value::I64(n) => numeral(&n.to_string()),
+        Value::F64(n) => scientific(*n, Some(5)),
+        Value::Bool(b) => boolean(*b),
+        Value::Str(s) => string(s),
+        Value::Tuple(values) => {
+            let values: Vec<_> = values.iter().map(self::value).collect();
+            parens(&values.join("<mo>,</mo>"))
+        }
+        Value::Module(m) => format!("<mi>{}</mi>", escape(m)),
+    }
+}
+
+/// Renders the expression as a single MathML element
+fn node(expr: &Expression) -> String {
+    match expr {
+        Expression::Value(expr) => value_expression(expr),
+        Expression::Fn(expr) => function(&expr.name, &expr.parameters),
+        Expression::Infix(expr) => infix(expr),
+        Expression::Prefix(expr) => {
+            let op = match expr.op {
+                PrefixOp::Not => "¬",
+                PrefixOp::Plus => "+",
+                PrefixOp::Minus => "-",
+                PrefixOp::PlusMinus => "±",
+                PrefixOp::BitNot => "~",
+            };
+            let rhs = operand(&expr.rhs, expr.op.rhs_needs_parens(&expr.rhs));
+            format!("<mrow><mo>{op}</mo>{rhs}</mrow>")
+        }
+        Expression::Comparison(expr) => {
+            let (first_op, _) = &expr.comparisons[0];
+            let mut mathml = operand(&expr.lhs, first_op.lhs_needs_parens(&expr.lhs));
+            for (i, (op, rhs)) in expr.comparisons.iter().enumerate() {
+                let rhs = operand(rhs, expr.rhs_needs_parens(i));
+                mathml.push_str(&format!("<mo>{}</mo>{rhs}", infix_op(op)));
+            }
+            format!("<mrow>{mathml}</mrow>")
+        }
+        Expression::Tuple(exprs) => {
+            let exprs: Vec<_> = exprs.iter().map(node).collect();
+            parens(&exprs.join("<mo>,</mo>"))
+        }
+        Expression::Series(expr) => series(expr),
+    }
+}
+
+fn value_expression(expr: &ValueExpression) -> String {
+    match expr {
+        ValueExpression::Bool(b) => boolean(*b),
+        ValueExpression::I64(n) => numeral(&n.to_string()),
+        ValueExpression::F64(n) => scientific(*n, None),
+        ValueExpression::Str(s) => string(s),
+        ValueExpression::Variable(v) => variable(v),
+    }
+}
+
+fn variable(name: &str) -> String {
+    if let Some(symbol) = variable_symbol(name) {
+        return format!("<mi>{symbol}</mi>");
+    }
+    // a subscripted variable like `x_1`
+    if let Some((base, subscript)) = name.split_once('_')
+        && base.chars().count() == 1
+        && !subscript.is_empty()
+    {
+        let subscript = match subscript.parse::<i64>() {
+            Ok(n) => numeral(&n.to_string()),
+            Err(_) => variable(subscript),
+        };
+        return format!("<msub>{}{subscript}</msub>", variable(base));
+    }
+    format!("<mi>{}</mi>", escape(name))
+}
+
+/// Renders a number, keeping a leading minus sign as an operator
+fn numeral(n: &str) -> String {
+    match n.strip_prefix('-') {
+        Some(n) => format!("<mrow><mo>-</mo><mn>{n}</mn></mrow>"),
+        None => format!("<mn>{n}</mn>"),
+    }
+}
+
+/// Renders a float, as `m × 10^e` when it's too large or small to write out
+fn scientific(n: f64, decimals: Option<usize>) -> String {
+    match float(n, decimals) {
+        (mantissa, Some(exponent)) => format!(
+            "<mrow>{}<mo>×</mo><msup><mn>10</mn>{}</msup></mrow>",
+            numeral(&mantissa),
+            numeral(&exponent)
+        ),
+        (n, None) => numeral(&n),
+    }
+}
+
+fn infix(expr: &InfixExpression) -> String {
+    let lhs = &expr.lhs;
+    let rhs = &expr.rhs;
+    match expr.op {
+        InfixOp::Divide => format!("<mfrac>{}{}</mfrac>", node(lhs), node(rhs)),
+        InfixOp::Exponent => {
+            // a fraction or `m × 10^e` base is parenthesized to show all of it is raised
+            let base_parens =
+                expr.op.lhs_needs_parens(lhs) || is_fraction(lhs) || is_scientific(lhs);
+            format!("<msup>{}{}</msup>", operand(lhs, base_parens), node(rhs))
+        }
+        InfixOp::Dot => match (lhs.as_ref(), rhs.as_ref()) {
+            // drop the module from `math.sin(x)`
+            (Expression::Value(ValueExpression::Variable(module)), _) if module == "math" => {
+                node(rhs)
+            }
+            // render a method call like `n.bit_count()` as `bit_count(n)`
+            (_, Expression::Fn(method)) => {
+                let mut parameters = vec![lhs.as_ref().clone()];
+                parameters.extend(method.parameters.iter().cloned());
+                function(&method.name, &parameters)
+            }
+            _ => format!("<mrow>{}<mo>.</mo>{}</mrow>", node(lhs), node(rhs)),
+        },
+        _ => {
+            // fractions are already grouped, so they never need parens
+            let lhs_parens = expr.op.lhs_needs_parens(lhs) && !is_fraction(lhs);
+            let rhs_parens = expr.op.rhs_needs_parens(rhs) && !is_fraction(rhs);
+            format!(
+                "<mrow>{}<mo>{}</mo>{}</mrow>",
+                operand(lhs, lhs_parens),
+                infix_op(&expr.op),
+                operand(rhs, rhs_parens)
+            )
+        }
+    }
+}
+
+fn infix_op(op: &InfixOp) -> &'static str {
+    match op {
+        InfixOp::Add => "+",
+        InfixOp::Subtract => "-",
+        InfixOp::PlusMinus => "±",
+        InfixOp::Multiply => "·",
+        InfixOp::Divide => "/",
+        InfixOp::Modulo => "mod",
+        InfixOp::Exponent => "^",
+        InfixOp::BitAnd => "&amp;",
+        InfixOp::BitOr => "|",
+        InfixOp::BitXor => "⊕",
+        InfixOp::ShiftLeft => "≪",
+        InfixOp::ShiftRight => "≫",
+        InfixOp::And => "∧",
+        InfixOp::Or => "∨",
+        InfixOp::Equal => "=",
+        InfixOp::NotEqual => "≠",
+        InfixOp::LessThan => "&lt;",
+        InfixOp::LessThanOrEqual => "≤",
+        InfixOp::GreaterThan => "&gt;",
+        InfixOp::GreaterThanOrEqual => "≥",
+        InfixOp::Is => "is",
+        InfixOp::IsNot => "is not",
+        InfixOp::In => "∈",
+        InfixOp::NotIn => "∉",
+        InfixOp::Dot => ".",
+    }
+}
+
+fn function(name: &str, parameters: &[Expression]) -> String {
+    match (name, parameters) {
+        ("sqrt", [x]) => format!("<msqrt>{}</msqrt>", node(x)),
+        ("cbrt", [x]) => format!("<mroot>{}<mn>3</mn></mroot>", node(x)),
+        ("abs", [x]) => format!("<mrow><mo>|</mo>{}<mo>|</mo></mrow>", node(x)),
+        ("factorial", [x]) => {
+            let (_, binding) = x.binding();
+            format!("<mrow>{}<mo>!</mo></mrow>", operand(x, binding < u8::MAX))
+        }
+        // the evaluator's one argument log is base 10
+        ("log", [x]) => format!(
+            "<mrow><msub><mi>log</mi><mn>10</mn></msub><mo>&#x2061;</mo>{}</mrow>",
+            parens(&node(x))
+        ),
+        ("log", [x, base]) => format!(
+            "<mrow><msub><mi>log</mi>{}</msub><mo>&#x2061;</mo>{}</mrow>",
+            node(base),
+            parens(&node(x))
+        ),
+        _ => {
+            let parameters: Vec<_> = parameters.iter().map(node).collect();
+            let name = function_word(name).unwrap_or(name);
+            format!(
+                "<mrow><mi>{}</mi><mo>&#x2061;</mo>{}</mrow>",
+                escape(name),
+                parens(&parameters.join("<mo>,</mo>"))
+            )
+        }
+    }
+}
+
+fn series(expr: &SeriesExpression) -> String {
+    let op = match expr.op {
+        SeriesOp::Sum => "∑",
+        SeriesOp::Product => "∏",
+    };
+    // the body of a series extends over one term, so a sum needs parens
+    let body_parens = expr.body.binding().0 < InfixOp::Multiply.precedence().0;
+    format!(
+        "<mrow><munderover><mo>{op}</mo><mrow>{}<mo>=</mo>{}</mrow>{}</munderover>{}</mrow>",
+        variable(&expr.variable),
+        node(&expr.lower),
+        node(&expr.upper),
+        operand(&expr.body, body_parens)
+    )
+}
+
+fn operand(expr: &Expression, needs_parens: bool) -> String {
+    if needs_parens {
+        parens(&node(expr))
+    } else {
+        node(expr)
+    }
+}
+
+fn parens(mathml: &str) -> String {
+    format!("<mrow><mo>(</mo>{mathml}<mo>)</mo></mrow>")
+}
+
+fn is_fraction(expr: &Expression) -> bool {
+    matches!(
+        expr,
+        Expression::Infix(InfixExpression {
+            op: InfixOp::Divide,
+            ..
+        })
+    )
+}
+
+fn boolean(b: bool) -> String {
+    if b {
+        "<mi mathvariant=\"normal\">True</mi>".to_string()
+    } else {
+        "<mi mathvariant=\"normal\">False</mi>".to_string()
+    }
+}
+
+fn string(s: &str) -> String {
+    format!("<ms>{}</ms>", escape(s))
+}
+
+/// Escapes characters with special meaning in XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        interpreter::value::Value,
+        parser::{self, ParseOptions},
+    };
+
+    #[test]
+    fn renders_expressions() {
+        let cases = [
+            (
+                "3 / 4 * sqrt(16)",
+                "<mrow><mfrac><mn>3</mn><mn>4</mn></mfrac><mo>·</mo><msqrt><mn>16</mn></msqrt></mrow>",
+            ),
+            (
+                "2 ** (x + 1)",
+                "<msup><mn>2</mn><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow></msup>",
+            ),
+            (
+                "abs(a - b)",
+                "<mrow><mo>|</mo><mrow><mi>a</mi><mo>-</mo><mi>b</mi></mrow><mo>|</mo></mrow>",
+            ),
+            (
+                "1 < x <= 10",
+                "<mrow><mn>1</mn><mo>&lt;</mo><mi>x</mi><mo>≤</mo><mn>10</mn></mrow>",
+            ),
+            (
+                "3 in (1, 2)",
+                "<mrow><mn>3</mn><mo>∈</mo><mrow><mo>(</mo><mn>1</mn><mo>,</mo><mn>2</mn><mo>)</mo></mrow></mrow>",
+            ),
+            (
+                "pi * x_1",
+                "<mrow><mi>π</mi><mo>·</mo><msub><mi>x</mi><mn>1</mn></msub></mrow>",
+            ),
+            ("0.25", "<mn>0.25</mn>"),
+            (
+                "1.5e300",
+                "<mrow><mn>1.5</mn><mo>×</mo><msup><mn>10</mn><mn>300</mn></msup></mrow>",
+            ),
+            (
+                "2.5e-7",
+                "<mrow><mn>2.5</mn><mo>×</mo><msup><mn>10</mn><mrow><mo>-</mo><mn>7</mn></mrow></msup></mrow>",
+            ),
+        ];
+        for (input, expected) in cases {
+            let expr = parser::parse(input, ParseOptions::default())
+                .unwrap()
+                .remove(0);
+            assert_eq!(super::node(&expr), expected, "rendering {input}");
+        }
+    }
+
+    #[test]
+    fn renders_values() {
+        let cases = [
+            (Value::F64(0.5), "<mn>0.50000</mn>"),
+            (
+                Value::F64(1.5e300),
+                "<mrow><mn>1.50000</mn><mo>×</mo><msup><mn>10</mn><mn>300</mn></msup></mrow>",
+            ),
+            (Value::I64(-3), "<mrow><mo>-</mo><mn>3</mn></mrow>"),
+            (
+                Value::F64(f64::NEG_INFINITY),
+                "<mrow><mo>-</mo><mi>∞</mi></mrow>",
+            ),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(super::value(&value), expected);
+        }
+    }
+}
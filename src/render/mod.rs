@@ -0,0 +1,102 @@
+use std::str::FromStr;
+
+use crate::{
+    interpreter::evaluator::Step,
+    parser::expression::{Expression, ValueExpression},
+};
+
+pub mod latex;
+pub mod mathml;
+
+/// How to typeset expressions and evaluation steps
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Typeset {
+    /// Plain python-style text
+    #[default]
+    Text,
+    Latex,
+    MathMl,
+}
+
+impl FromStr for Typeset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Typeset::Text),
+            "latex" => Ok(Typeset::Latex),
+            "mathml" => Ok(Typeset::MathMl),
+            _ => Err(format!(
+                "unknown typeset {s}, expected text, latex or mathml"
+            )),
+        }
+    }
+}
+
+/// Renders the expression
+pub fn expression(expr: &Expression, typeset: Typeset) -> String {
+    match typeset {
+        Typeset::Text => expr.to_string(),
+        Typeset::Latex => latex::expression(expr),
+        Typeset::MathMl => mathml::expression(expr),
+    }
+}
+
+/// Renders an evaluation step as `expression => value`
+pub fn step(step: &Step, typeset: Typeset) -> String {
+    match typeset {
+        Typeset::Text => step.to_string(),
+        Typeset::Latex => latex::step(step),
+        Typeset::MathMl => mathml::step(step),
+    }
+}
+
+/// Returns the display name of a variable, like `π` for `pi`
+fn variable_symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "pi" | "PI" | "π" => "π",
+        "tau" | "TAU" | "τ" => "τ",
+        "φ" | "ϕ" => "φ",
+        "inf" | "∞" => "∞",
+        _ => return None,
+    })
+}
+
+/// Returns the display name of a function whose name is a word rather than a symbol,
+/// like `sin` or `arcsin` for `asin`
+fn function_word(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "sin" => "sin",
+        "cos" => "cos",
+        "tan" => "tan",
+        "asin" => "arcsin",
+        "acos" => "arccos",
+        "atan" => "arctan",
+        "ln" => "ln",
+        "log" => "log",
+        "exp" => "exp",
+        _ => return None,
+    })
+}
+
+/// Returns true if the expression is a float written with a power of ten
+fn is_scientific(expr: &Expression) -> bool {
+    matches!(expr, Expression::Value(ValueExpression::F64(n)) if float(*n, None).1.is_some())
+}
+
+/// Formats a finite float as a mantissa, and the power of ten it's multiplied by when
+/// it's too large or small to write out, from the magnitudes where Python switches to
+/// `1.5e+300`. With `decimals`, the mantissa is rounded like the evaluator prints values.
+pub(crate) fn float(n: f64, decimals: Option<usize>) -> (String, Option<String>) {
+    let written_out = n == 0.0 || (1e-4..1e16).contains(&n.abs());
+    let formatted = match decimals {
+        Some(decimals) if written_out => format!("{n:.decimals$}"),
+        Some(decimals) => format!("{n:.decimals$e}"),
+        None if written_out => n.to_string(),
+        None => format!("{n:e}"),
+    };
+    match formatted.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa.to_string(), Some(exponent.to_string())),
+        None => (formatted, None),
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+};
+
+use serde::Serialize;
+
+use crate::{
+    agent::Answer,
+    interpreter::evaluator::Evaluator,
+    parser::{self, ParseOptions},
+};
+
+/// How an answer was worked out, step by step, as the servers return it
+#[derive(Debug, Serialize)]
+pub struct Report {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub question: Option<String>,
+    pub expression: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, serde_json::Number>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    pub steps: Vec<StepReport>,
+    /// The value of the last expression, unless evaluating failed
+    pub value: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StepReport {
+    pub expression: String,
+    pub value: serde_json::Value,
+}
+
+impl Report {
+    /// Evaluates an expression without a question, on a blocking thread like
+    /// [Report::answer_isolated], since it comes from the network or a model's tool call
+    pub async fn evaluate_isolated(expression: String, options: ParseOptions) -> Self {
+        let answer = Answer {
+            expression,
+            variables: BTreeMap::new(),
+            explanation: None,
+        };
+        Self::answer_isolated(None, answer, options).await
+    }
+
+    /// Like [Report::answer], but on a blocking thread so a long evaluation doesn't hold up
+    /// the async runtime, and reporting a panic as an error rather than unwinding into the caller
+    pub async fn answer_isolated(
+        question: Option<String>,
+        answer: Answer,
+        options: ParseOptions,
+    ) -> Self {
+        let mut report = Self::empty(question.as_deref(), &answer);
+        let result = tokio::task::spawn_blocking(move || {
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                Self::answer(question.as_deref(), answer, options)
+            }))
+        })
+        .await;
+        match result {
+            Ok(Ok(answered)) => return answered,
+            Ok(Err(payload)) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown error");
+                report.error = Some(format!("evaluation panicked: {message}"));
+            }
+            Err(error) => report.error = Some(error.to_string()),
+        }
+        report
+    }
+
+    /// Evaluates the model's answer to the question, stopping at the first error
+    pub fn answer(question: Option<&str>, answer: Answer, options: ParseOptions) -> Self {
+        let mut report = Self::empty(question, &answer);
+        let expressions = match parser::parse(&answer.expression, options) {
+            Ok(expressions) => expressions,
+            Err(error) => {
+                report.error = Some(error.to_string());
+                return report;
+            }
+        };
+        for expr in expressions {
+            let mut evaluator = Evaluator::new(answer.substitute(expr));
+            let result = evaluator.eval();
+            report
+                .steps
+                .extend(evaluator.steps().iter().map(|step| StepReport {
+                    expression: step.expression.to_string(),
+                    value: (&step.value).into(),
+                }));
+            match result {
+                Ok(value) => report.value = Some((&value).into()),
+                Err(error) => {
+                    report.value = None;
+                    report.error = Some(error.to_string());
+                    return report;
+                }
+            }
+        }
+        report
+    }
+
+    /// A report of the answer with nothing evaluated yet
+    fn empty(question: Option<&str>, answer: &Answer) -> Self {
+        Report {
+            question: question.map(str::to_string),
+            expression: answer.expression.clone(),
+            variables: answer.variables.clone(),
+            explanation: answer.explanation.clone(),
+            steps: vec![],
+            value: None,
+            error: None,
+        }
+    }
+}
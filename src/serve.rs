@@ -0,0 +1,228 @@
+use std::{convert::Infallible, sync::Arc};
+
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Bytes, Incoming},
+    header,
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{net::TcpListener, sync::Semaphore};
+
+use crate::{
+    MLResult,
+    agent::{AgentClient, Conversation, call_agent},
+    interpreter::evaluator::FUNCTIONS,
+    parser::ParseOptions,
+    report::Report,
+};
+
+/// The largest request body the server reads
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+struct SolveRequest {
+    question: String,
+}
+
+#[derive(Deserialize)]
+struct EvaluateRequest {
+    expression: String,
+}
+
+/// Answers HTTP requests with one model client shared between them
+pub struct Server {
+    client: Arc<dyn AgentClient>,
+    /// The system prompt and examples every question starts from
+    conversation: Conversation,
+    options: ParseOptions,
+    /// Limits how many questions are put to the model at once
+    permits: Semaphore,
+}
+
+impl Server {
+    pub fn new(
+        client: Arc<dyn AgentClient>,
+        conversation: Conversation,
+        options: ParseOptions,
+        max_concurrent: usize,
+    ) -> Self {
+        Self {
+            client,
+            conversation,
+            options,
+            permits: Semaphore::new(max_concurrent.max(1)),
+        }
+    }
+
+    /// Accepts connections until the process is stopped
+    pub async fn listen(self: Arc<Self>, host: &str, port: u16) -> MLResult<()> {
+        let listener = TcpListener::bind((host, port)).await?;
+        eprintln!("Listening on http://{}", listener.local_addr()?);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(|request| server.clone().handle(request));
+                let connection =
+                    http1::Builder::new().serve_connection(TokioIo::new(stream), service);
+                if let Err(error) = connection.await {
+                    tracing::debug!("connection failed: {error}");
+                }
+            });
+        }
+    }
+
+    async fn handle(
+        self: Arc<Self>,
+        request: Request<Incoming>,
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let body = match Limited::new(request.into_body(), MAX_BODY_LEN)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(error) => {
+                let body = json!({ "error": error.to_string() });
+                return Ok(response(StatusCode::BAD_REQUEST, &body));
+            }
+        };
+        let (status, body) = self.route(&method, &path, &body).await;
+        Ok(response(status, &body))
+    }
+
+    /// Answers a request, returning the status and JSON body of the response
+    async fn route(
+        &self,
+        method: &Method,
+        path: &str,
+        body: &[u8],
+    ) -> (StatusCode, serde_json::Value) {
+        match (method, path) {
+            (&Method::POST, "/solve") => match serde_json::from_slice::<SolveRequest>(body) {
+                Ok(request) => self.solve(&request.question).await,
+                Err(error) => bad_request(error),
+            },
+            (&Method::POST, "/evaluate") => match serde_json::from_slice::<EvaluateRequest>(body) {
+                Ok(request) => {
+                    let report = Report::evaluate_isolated(request.expression, self.options).await;
+                    (StatusCode::OK, json!(report))
+                }
+                Err(error) => bad_request(error),
+            },
+            (&Method::GET, "/functions") => {
+                let functions: Vec<_> = FUNCTIONS
+                    .iter()
+                    .map(|(signature, description)| {
+                        json!({ "signature": signature, "description": description })
+                    })
+                    .collect();
+                (StatusCode::OK, json!(functions))
+            }
+            (_, "/solve" | "/evaluate" | "/functions") => (
+                StatusCode::METHOD_NOT_ALLOWED,
+                json!({ "error": format!("{method} is not allowed on {path}") }),
+            ),
+            _ => (
+                StatusCode::NOT_FOUND,
+                json!({ "error": format!("no route for {path}") }),
+            ),
+        }
+    }
+
+    async fn solve(&self, question: &str) -> (StatusCode, serde_json::Value) {
+        let mut conversation = self.conversation.clone();
+        conversation.ask(question);
+        let answer = {
+            let _permit = self
+                .permits
+                .acquire()
+                .await
+                .expect("the semaphore is never closed");
+            call_agent(self.client.as_ref(), &conversation).await
+        };
+        match answer {
+            Ok(answer) => {
+                let report =
+                    Report::answer_isolated(Some(question.to_string()), answer, self.options).await;
+                (StatusCode::OK, json!(report))
+            }
+            Err(error) => (
+                StatusCode::BAD_GATEWAY,
+                json!({ "question": question, "error": error.to_string() }),
+            ),
+        }
+    }
+}
+
+fn bad_request(error: serde_json::Error) -> (StatusCode, serde_json::Value) {
+    (
+        StatusCode::BAD_REQUEST,
+        json!({ "error": error.to_string() }),
+    )
+}
+
+fn response(status: StatusCode, body: &serde_json::Value) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body.to_string())));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{mock::MockAgentClient, template::PromptTemplate};
+
+    #[tokio::test]
+    async fn routes_requests() {
+        let client = MockAgentClient::new([(
+            "a shirt costs $80 and is 25% off, what do I pay?",
+            r#"{"expression": "price * (1 - discount)", "variables": {"price": 80, "discount": 0.25}}"#,
+        )]);
+        let conversation = Conversation::new(&PromptTemplate::default()).unwrap();
+        let server = Server::new(Arc::new(client), conversation, ParseOptions::default(), 1);
+
+        let body = br#"{"question": "a shirt costs $80 and is 25% off, what do I pay?"}"#;
+        let (status, report) = server.route(&Method::POST, "/solve", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["value"], json!(60.0));
+        assert_eq!(report["steps"][0]["expression"], "1 - 0.25");
+
+        let body = br#"{"expression": "frobnicate(2 - 2)"}"#;
+        let (status, report) = server.route(&Method::POST, "/evaluate", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["steps"][0]["value"], json!(0));
+        assert!(report["error"].is_string());
+
+        let body = br#"{"expression": "1 // 0"}"#;
+        let (status, report) = server.route(&Method::POST, "/evaluate", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["error"], "division by zero");
+
+        let body = json!({ "expression": "(".repeat(30_000) }).to_string();
+        let (status, report) = server
+            .route(&Method::POST, "/evaluate", body.as_bytes())
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(report["error"].as_str().unwrap().contains("nested"));
+
+        let (status, functions) = server.route(&Method::GET, "/functions", b"").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(functions[0]["signature"], "sin(x)");
+
+        let (status, _) = server.route(&Method::POST, "/solve", b"{}").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = server.route(&Method::GET, "/solve", b"").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tokio::task::JoinSet;
+
+use crate::{
+    MLResult,
+    agent::{AgentClient, Answer, Conversation, call_agent},
+    interpreter::value::Value,
+    parser::ParseOptions,
+};
+
+/// One of several translations of the same request, and what it evaluated to
+pub struct Sample {
+    /// The model's answer, unless the model couldn't be reached
+    pub answer: Option<Answer>,
+    pub result: MLResult<Value>,
+}
+
+impl Sample {
+    pub fn expression(&self) -> &str {
+        self.answer
+            .as_ref()
+            .map_or("", |answer| answer.expression.as_str())
+    }
+}
+
+/// The samples, grouped by the answer they agree on
+pub struct Vote {
+    pub samples: Vec<Sample>,
+    /// The answer most samples agree on, if any sample evaluated
+    pub majority: Option<Value>,
+    /// How many samples agree with the majority
+    pub agreeing: usize,
+}
+
+impl Vote {
+    /// Counts the samples that agree on each answer. Answers agree if they print the same,
+    /// so floats that differ past the printed precision count as one answer.
+    pub fn tally(samples: Vec<Sample>) -> Self {
+        let mut counts: Vec<(String, &Value, usize)> = vec![];
+        for sample in &samples {
+            let Ok(value) = &sample.result else {
+                continue;
+            };
+            let key = value.to_string();
+            match counts.iter_mut().find(|(k, _, _)| *k == key) {
+                Some((_, _, count)) => *count += 1,
+                None => counts.push((key, value, 1)),
+            }
+        }
+        // the earliest answer wins a tie
+        let majority = counts
+            .iter()
+            .rev()
+            .max_by_key(|(_, _, count)| *count)
+            .map(|(_, value, count)| ((*value).clone(), *count));
+        let (majority, agreeing) = match majority {
+            Some((value, count)) => (Some(value), count),
+            None => (None, 0),
+        };
+        Vote {
+            samples,
+            majority,
+            agreeing,
+        }
+    }
+
+    /// The fraction of samples that agree with the majority
+    pub fn agreement(&self) -> f64 {
+        self.agreeing as f64 / self.samples.len() as f64
+    }
+
+    /// The answer of the first sample that agrees with the majority
+    pub fn majority_answer(&self) -> Option<&Answer> {
+        let majority = self.majority.as_ref()?.to_string();
+        self.samples
+            .iter()
+            .find(|sample| {
+                sample
+                    .result
+                    .as_ref()
+                    .is_ok_and(|v| v.to_string() == majority)
+            })
+            .and_then(|sample| sample.answer.as_ref())
+    }
+
+    /// The samples that failed or disagree with the majority
+    pub fn dissenting(&self) -> impl Iterator<Item = &Sample> {
+        let majority = self.majority.as_ref().map(Value::to_string);
+        self.samples
+            .iter()
+            .filter(move |sample| sample.result.as_ref().map(Value::to_string).ok() != majority)
+    }
+}
+
+/// Asks the model to translate the last request in the conversation `count` times at once,
+/// and evaluates each translation. A sample whose task panics counts as a failed sample.
+pub async fn sample(
+    client: Arc<dyn AgentClient>,
+    conversation: &Conversation,
+    options: ParseOptions,
+    count: usize,
+) -> Vec<Sample> {
+    let mut tasks = JoinSet::new();
+    // the index of each task's sample, for placing a task that panicked
+    let mut spawned = HashMap::new();
+    for i in 0..count {
+        let conversation = conversation.clone();
+        let client = client.clone();
+        let task = tasks.spawn(async move {
+            let sample = match call_agent(client.as_ref(), &conversation).await {
+                Ok(answer) => Sample {
+                    result: answer.evaluate(options),
+                    answer: Some(answer),
+                },
+                Err(error) => Sample {
+                    answer: None,
+                    result: Err(error),
+                },
+            };
+            (i, sample)
+        });
+        spawned.insert(task.id(), i);
+    }
+    let mut samples = vec![];
+    while let Some(joined) = tasks.join_next().await {
+        samples.push(match joined {
+            Ok(sampled) => sampled,
+            Err(error) => {
+                let i = spawned[&error.id()];
+                let sample = Sample {
+                    answer: None,
+                    result: Err(error.into()),
+                };
+                (i, sample)
+            }
+        });
+    }
+    // keep the order the samples were requested in, so ties break the same way
+    samples.sort_by_key(|(i, _)| *i);
+    samples.into_iter().map(|(_, sample)| sample).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{
+        agent::{Prompt, template::PromptTemplate},
+        error::MathlineError,
+    };
+
+    fn sample(expression: &str, result: MLResult<Value>) -> Sample {
+        Sample {
+            answer: Some(Answer::from_response(&format!(
+                r#"{{"expression": "{expression}", "variables": {{}}}}"#
+            ))),
+            result,
+        }
+    }
+
+    #[test]
+    fn tallies_samples() {
+        let vote = Vote::tally(vec![
+            sample("1 / 3", Ok(Value::F64(1.0 / 3.0))),
+            sample("2 ** 10", Ok(Value::I64(1024))),
+            sample("0.333333", Ok(Value::F64(0.333333))),
+            sample("1 // 0", Err(MathlineError::DivisionByZero)),
+            sample("1024", Ok(Value::I64(1024))),
+        ]);
+        // the earliest of the tied answers wins, and floats agree up to printed precision
+        assert_eq!(
+            vote.majority.as_ref().map(Value::to_string).unwrap(),
+            "0.33333"
+        );
+        assert_eq!(vote.agreeing, 2);
+        assert_eq!(vote.agreement(), 0.4);
+        assert_eq!(vote.majority_answer().unwrap().expression, "1 / 3");
+        let dissenting: Vec<_> = vote.dissenting().map(Sample::expression).collect();
+        assert_eq!(dissenting, ["2 ** 10", "1 // 0", "1024"]);
+
+        let vote = Vote::tally(vec![sample("1 // 0", Err(MathlineError::DivisionByZero))]);
+        assert!(vote.majority.is_none());
+        assert_eq!(vote.agreeing, 0);
+        assert!(vote.majority_answer().is_none());
+    }
+
+    /// Answers `2 ** 10`, except that its second call panics
+    struct PanickingClient(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl AgentClient for PanickingClient {
+        async fn messages<'a>(&self, _prompt: Prompt<'a>) -> MLResult<String> {
+            if self.0.fetch_add(1, Ordering::SeqCst) == 1 {
+                panic!("the client fell over");
+            }
+            Ok(r#"{"expression": "2 ** 10", "variables": {}}"#.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn counts_panicked_samples_as_failures() {
+        let mut conversation = Conversation::new(&PromptTemplate::default()).unwrap();
+        conversation.ask("what is two to the tenth?");
+        let client = Arc::new(PanickingClient(AtomicUsize::new(0)));
+        let samples = super::sample(client, &conversation, ParseOptions::default(), 3).await;
+        assert_eq!(samples.len(), 3);
+        let failed: Vec<_> = samples.iter().filter(|s| s.result.is_err()).collect();
+        assert_eq!(failed.len(), 1);
+        assert!(matches!(failed[0].result, Err(MathlineError::Join(_))));
+
+        let vote = Vote::tally(samples);
+        assert!(matches!(vote.majority, Some(Value::I64(1024))));
+        assert_eq!(vote.agreeing, 2);
+        assert_eq!(vote.dissenting().count(), 1);
+    }
+}
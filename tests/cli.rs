@@ -0,0 +1,130 @@
+//! Runs the mathline binary against the recorded model responses in `tests/fixtures`
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn mathline(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mathline"))
+        .args([
+            "--replay",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"),
+        ])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn solves_requests() {
+    let output = mathline(&["what is two to the tenth?"], "");
+    assert_eq!(
+        stdout(&output),
+        "LLM: 2 ** 10\n\
+         Explanation: two raised to the tenth power\n\
+         Parse: 2 ** 10\n\
+         Step: 2 ** 10 => 1024\n\
+         Answer: 1024\n"
+    );
+}
+
+#[test]
+fn substitutes_variables() {
+    let output = mathline(&["a shirt costs $80 and is 25% off, what do I pay?"], "");
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Parse: 80 * (1 - 0.25)\n"), "{stdout}");
+    assert!(stdout.ends_with("Answer: 60.00000\n"), "{stdout}");
+}
+
+#[test]
+fn reports_evaluation_errors() {
+    let output = mathline(&["what is the frobnicate of three?"], "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("ERROR: function frobnicate is not supported"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn chats_about_earlier_answers() {
+    let output = mathline(
+        &["what is two to the tenth?", "--chat"],
+        "now double that\n",
+    );
+    let answers: Vec<_> = stdout(&output)
+        .lines()
+        .filter(|line| line.starts_with("Answer: "))
+        .map(str::to_string)
+        .collect();
+    assert_eq!(answers, ["Answer: 1024", "Answer: 2048"]);
+}
+
+#[test]
+fn streams_and_votes_offline() {
+    let output = mathline(&["what is two to the tenth?", "--stream"], "");
+    assert!(stdout(&output).ends_with("Answer: 1024\n"));
+
+    let output = mathline(&["what is two to the tenth?", "--samples", "3"], "");
+    assert!(stdout(&output).contains("Agreement: 3/3 (100%)\n"));
+}
+
+#[test]
+fn verifies_answers() {
+    let output = mathline(&["what is two to the tenth?", "--verify"], "");
+    assert!(stdout(&output).ends_with(
+        "Answer: 1024\n\
+         Described: two raised to the power of ten\n\
+         Verified: the expression matches the request\n"
+    ));
+}
+
+#[test]
+fn answers_batches_in_order() {
+    let questions = "what is two to the tenth?\n\
+                     what is the frobnicate of three?\n\
+                     \n\
+                     a shirt costs $80 and is 25% off, what do I pay?\n";
+    let output = mathline(&["--batch", "-", "--concurrency", "2"], questions);
+    assert_eq!(
+        stdout(&output),
+        "what is two to the tenth? => 1024\n\
+         what is the frobnicate of three? => ERROR: function frobnicate is not supported\n\
+         a shirt costs $80 and is 25% off, what do I pay? => 60.00000\n"
+    );
+
+    let output = mathline(&["--batch", "-", "--format", "csv"], questions);
+    assert_eq!(
+        stdout(&output),
+        "question,expression,value,error\n\
+         what is two to the tenth?,2 ** 10,1024,\n\
+         what is the frobnicate of three?,frobnicate(3),,function frobnicate is not supported\n\
+         \"a shirt costs $80 and is 25% off, what do I pay?\",price * (1 - discount),60.00000,\n"
+    );
+}
+
+#[test]
+fn continues_batches_past_failures() {
+    let output = mathline(&["--batch", "-"], "a\nb\n");
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "a => ERROR: division by zero\n\
+         b => 4\n"
+    );
+}